@@ -0,0 +1,89 @@
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group, hook},
+    },
+    model::channel::Message,
+};
+
+use crate::{config, send_msg, settings};
+
+const MAX_PREFIX_LENGTH: usize = 10;
+
+#[group]
+#[commands(prefix)]
+pub struct Admin;
+
+// returns the prefix of the guild the message was sent in, or the default prefix
+#[hook]
+pub async fn dynamic_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    let guild_prefix = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        msg.guild_id
+            .and_then(|guild_id| settings.guild(guild_id))
+            .and_then(|guild_settings| guild_settings.prefix.clone())
+    };
+
+    match guild_prefix {
+        Some(prefix) => Some(prefix),
+        None => Some(config::get(ctx).await.prefix.clone()),
+    }
+}
+
+// shows the command prefix of the current guild
+#[command]
+#[only_in(guilds)]
+#[sub_commands(prefix_set, prefix_reset)]
+async fn prefix(ctx: &Context, msg: &Message) -> CommandResult {
+    let prefix = dynamic_prefix(ctx, msg).await.unwrap_or_default();
+    send_msg(ctx, msg, &format!("The command prefix in this server is `{}` (mentioning the bot works too)", prefix)).await;
+
+    Ok(())
+}
+
+// sets the command prefix of the current guild
+#[command("set")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn prefix_set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let prefix = args.rest().trim();
+    if prefix.is_empty() || prefix.chars().any(char::is_whitespace) || prefix.chars().count() > MAX_PREFIX_LENGTH {
+        send_msg(ctx, msg, &format!("Usage: `prefix set <prefix>` (up to {} characters, no spaces)", MAX_PREFIX_LENGTH)).await;
+        return Ok(());
+    }
+
+    set_impl(ctx, msg, Some(prefix.to_string())).await
+}
+
+// restores the default command prefix of the current guild
+#[command("reset")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn prefix_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    set_impl(ctx, msg, None).await
+}
+
+async fn set_impl(ctx: &Context, msg: &Message, prefix: Option<String>) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.guild_mut(guild_id).prefix = prefix.clone();
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    let prefix = match prefix {
+        Some(prefix) => prefix,
+        None => config::get(ctx).await.prefix.clone(),
+    };
+    send_msg(ctx, msg, &format!("Command prefix set to `{}`", prefix)).await;
+
+    Ok(())
+}
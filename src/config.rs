@@ -0,0 +1,337 @@
+use serde::Deserialize;
+use serenity::{model::prelude::UserId, prelude::*};
+use std::{collections::HashSet, env, fs, path::{Path, PathBuf}, sync::Arc};
+use structopt::StructOpt;
+use tracing_subscriber::EnvFilter;
+
+use crate::{Result, ResultExt};
+
+// the config file that is used if `--config` isn't passed, if it exists
+const DEFAULT_CONFIG_PATH: &str = "rybot2.toml";
+const DEFAULT_PREFIX: &str = "~";
+const DEFAULT_VOICE_IDLE_TIMEOUT: u64 = 5;
+const DEFAULT_MAX_PLAYLIST_TRACKS: usize = 50;
+const DEFAULT_SEARCH_TIMEOUT: u64 = 30;
+const DEFAULT_VOTESKIP_PERCENT: u32 = 50;
+const DEFAULT_MAX_TRACK_DURATION: u64 = 60;
+const DEFAULT_MAX_USER_TRACKS: usize = 25;
+const DEFAULT_MAX_QUEUE_LENGTH: usize = 200;
+
+pub struct ConfigContainer;
+impl TypeMapKey for ConfigContainer {
+    type Value = Arc<Config>;
+}
+
+#[derive(StructOpt)]
+#[structopt(about = "A simple Discord bot")]
+struct Options {
+    /// Path to a TOML config file [default: rybot2.toml, if it exists]
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Discord bot token [default: the DISCORD_TOKEN environment variable]
+    #[structopt(long)]
+    token: Option<String>,
+
+    /// File containing the Discord bot token
+    #[structopt(long, parse(from_os_str))]
+    token_file: Option<PathBuf>,
+
+    /// Default command prefix, used in servers that haven't set their own [default: ~]
+    #[structopt(long)]
+    prefix: Option<String>,
+
+    /// User ID of a bot owner, may be passed multiple times [default: the owner of the bot application]
+    #[structopt(long = "owner")]
+    owners: Vec<u64>,
+
+    /// Log filter, for example `info` or `rybot2=debug` [default: the RUST_LOG environment variable]
+    #[structopt(long)]
+    log_level: Option<String>,
+
+    /// Directory where settings are stored [default: .]
+    #[structopt(long, parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+
+    /// Command group to enable, may be passed multiple times [default: all groups]
+    #[structopt(long = "group")]
+    groups: Vec<String>,
+
+    /// Minutes without audio before leaving a voice channel, 0 to never leave, unless a server set its own [default: 5]
+    #[structopt(long)]
+    voice_idle_timeout: Option<u64>,
+
+    /// Maximum number of tracks queued from a single playlist [default: 50]
+    #[structopt(long)]
+    max_playlist_tracks: Option<usize>,
+
+    /// Seconds to wait for a choice after `search` lists its results [default: 30]
+    #[structopt(long)]
+    search_timeout: Option<u64>,
+
+    /// Percentage of listeners in the voice channel that must vote to skip a track with `voteskip` [default: 50]
+    #[structopt(long)]
+    voteskip_percent: Option<u32>,
+
+    /// Maximum length in minutes of a queued track, 0 for no limit [default: 60]
+    #[structopt(long)]
+    max_track_duration: Option<u64>,
+
+    /// Maximum number of tracks a user can have in a server's queue, 0 for no limit [default: 25]
+    #[structopt(long)]
+    max_user_tracks: Option<usize>,
+
+    /// Maximum number of tracks in a server's queue, 0 for no limit [default: 200]
+    #[structopt(long)]
+    max_queue_length: Option<usize>,
+
+    /// Directory of audio files that can be played with `play file:<name>` [default: none]
+    #[structopt(long, parse(from_os_str))]
+    music_dir: Option<PathBuf>,
+}
+
+// the contents of the config file, every value can be overridden by the corresponding flag
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    token: Option<String>,
+    token_file: Option<PathBuf>,
+    prefix: Option<String>,
+    owners: Option<Vec<u64>>,
+    log_level: Option<String>,
+    data_dir: Option<PathBuf>,
+    groups: Option<Vec<String>>,
+    voice_idle_timeout: Option<u64>,
+    max_playlist_tracks: Option<usize>,
+    search_timeout: Option<u64>,
+    voteskip_percent: Option<u32>,
+    max_track_duration: Option<u64>,
+    max_user_tracks: Option<usize>,
+    max_queue_length: Option<usize>,
+    music_dir: Option<PathBuf>,
+}
+
+pub struct Config {
+    pub token: String,
+    pub prefix: String,
+    // empty if the owners should be looked up from the bot application
+    pub owners: HashSet<UserId>,
+    pub log_filter: Option<String>,
+    pub data_dir: PathBuf,
+    // lowercase command group names, empty if every group is enabled
+    pub groups: Vec<String>,
+    // in minutes, 0 means never
+    pub voice_idle_timeout: u64,
+    pub max_playlist_tracks: usize,
+    // in seconds
+    pub search_timeout: u64,
+    // 1 to 100
+    pub voteskip_percent: u32,
+    // the queue limits are 0 if there's no limit, the track duration is in minutes
+    pub max_track_duration: u64,
+    pub max_user_tracks: usize,
+    pub max_queue_length: usize,
+    // `None` if the music library is disabled
+    pub music_dir: Option<PathBuf>,
+}
+
+impl Config {
+    // builds the config from the command line flags and the config file, flags taking precedence
+    pub fn load() -> Result<Config> {
+        Config::from_options(Options::from_args())
+    }
+
+    fn from_options(options: Options) -> Result<Config> {
+        let file = match &options.config {
+            Some(path) => load_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => load_file(Path::new(DEFAULT_CONFIG_PATH))?,
+            None => ConfigFile::default(),
+        };
+
+        let token = if let Some(token) = options.token {
+            token
+        } else if let Some(path) = &options.token_file {
+            read_token_file(path)?
+        } else if let Some(token) = file.token {
+            token
+        } else if let Some(path) = &file.token_file {
+            read_token_file(path)?
+        } else if let Ok(token) = env::var("DISCORD_TOKEN") {
+            token
+        } else {
+            return Err("No Discord token provided (use `--token`, `--token-file`, `token` or `token_file` in the config file, or the DISCORD_TOKEN environment variable)".into());
+        };
+        let token = token.trim().to_string();
+        if token.is_empty() {
+            return Err("The Discord token is empty".into());
+        }
+
+        let prefix = options.prefix.or(file.prefix).unwrap_or_else(|| DEFAULT_PREFIX.to_string());
+        if prefix.is_empty() || prefix.chars().any(char::is_whitespace) {
+            return Err(format!("Invalid prefix `{}`: the prefix must not be empty or contain whitespace", prefix).into());
+        }
+
+        let owners = if options.owners.is_empty() { file.owners.unwrap_or_default() } else { options.owners };
+        let owners = owners.into_iter().map(UserId).collect();
+
+        let log_filter = options.log_level.or(file.log_level);
+        if let Some(log_filter) = &log_filter {
+            if let Err(reason) = EnvFilter::try_new(log_filter) {
+                return Err(format!("Invalid log level `{}`: {}", log_filter, reason).into());
+            }
+        }
+
+        let data_dir = options.data_dir.or(file.data_dir).unwrap_or_else(|| PathBuf::from("."));
+        fs::create_dir_all(&data_dir).chain_err(|| format!("Unable to create data directory {:?}", data_dir))?;
+
+        let groups = if options.groups.is_empty() { file.groups.unwrap_or_default() } else { options.groups };
+        let groups = groups.iter().map(|group| group.to_lowercase()).collect();
+
+        let voice_idle_timeout = options.voice_idle_timeout.or(file.voice_idle_timeout).unwrap_or(DEFAULT_VOICE_IDLE_TIMEOUT);
+
+        let max_playlist_tracks = options.max_playlist_tracks.or(file.max_playlist_tracks).unwrap_or(DEFAULT_MAX_PLAYLIST_TRACKS);
+        if max_playlist_tracks == 0 {
+            return Err("Invalid max_playlist_tracks: at least 1 track must be allowed".into());
+        }
+
+        let search_timeout = options.search_timeout.or(file.search_timeout).unwrap_or(DEFAULT_SEARCH_TIMEOUT);
+        if search_timeout == 0 {
+            return Err("Invalid search_timeout: the timeout must be at least 1 second".into());
+        }
+
+        let voteskip_percent = options.voteskip_percent.or(file.voteskip_percent).unwrap_or(DEFAULT_VOTESKIP_PERCENT);
+        if !(1..=100).contains(&voteskip_percent) {
+            return Err("Invalid voteskip_percent: the percentage must be between 1 and 100".into());
+        }
+
+        let max_track_duration = options.max_track_duration.or(file.max_track_duration).unwrap_or(DEFAULT_MAX_TRACK_DURATION);
+        let max_user_tracks = options.max_user_tracks.or(file.max_user_tracks).unwrap_or(DEFAULT_MAX_USER_TRACKS);
+        let max_queue_length = options.max_queue_length.or(file.max_queue_length).unwrap_or(DEFAULT_MAX_QUEUE_LENGTH);
+
+        let music_dir = options.music_dir.or(file.music_dir);
+        if let Some(music_dir) = &music_dir {
+            if !music_dir.is_dir() {
+                return Err(format!("Invalid music_dir {:?}: not a directory", music_dir).into());
+            }
+        }
+
+        Ok(Config {
+            token,
+            prefix,
+            owners,
+            log_filter,
+            data_dir,
+            groups,
+            voice_idle_timeout,
+            max_playlist_tracks,
+            search_timeout,
+            voteskip_percent,
+            max_track_duration,
+            max_user_tracks,
+            max_queue_length,
+            music_dir,
+        })
+    }
+
+    pub fn group_enabled(&self, name: &str) -> bool {
+        self.groups.is_empty() || self.groups.iter().any(|group| group.eq_ignore_ascii_case(name))
+    }
+}
+
+fn load_file(path: &Path) -> Result<ConfigFile> {
+    let content = fs::read_to_string(path).chain_err(|| format!("Unable to read config file {:?}", path))?;
+    toml::from_str(&content).chain_err(|| format!("Invalid config file {:?}", path))
+}
+
+fn read_token_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).chain_err(|| format!("Unable to read token file {:?}", path))
+}
+
+// retrieves the config stored in the client data
+pub async fn get(ctx: &Context) -> Arc<Config> {
+    let data = ctx.data.read().await;
+    data.get::<ConfigContainer>().expect("Error getting config").clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every config needs a token and a data directory that can be created, an empty config file keeps a local rybot2.toml out
+    fn load_with(flags: &[&str]) -> Result<Config> {
+        let data_dir = env::temp_dir().join("rybot2-config-test");
+        let data_dir = data_dir.to_str().unwrap();
+        let empty_file = write_file("empty", "");
+        let mut args = vec!["rybot2", "--token", "token", "--data-dir", data_dir];
+        if !flags.contains(&"--config") {
+            args.extend_from_slice(&["--config", empty_file.to_str().unwrap()]);
+        }
+        args.extend_from_slice(flags);
+        Config::from_options(Options::from_iter(args))
+    }
+
+    fn write_file(name: &str, content: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rybot2-config-test-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn defaults_are_used_without_flags() {
+        let config = load_with(&[]).unwrap();
+        assert_eq!(config.prefix, DEFAULT_PREFIX);
+        assert_eq!(config.voteskip_percent, DEFAULT_VOTESKIP_PERCENT);
+        assert_eq!(config.max_queue_length, DEFAULT_MAX_QUEUE_LENGTH);
+        assert!(config.group_enabled("voice"));
+    }
+
+    #[test]
+    fn invalid_prefixes_are_rejected() {
+        assert!(load_with(&["--prefix", ""]).is_err());
+        assert!(load_with(&["--prefix", "a b"]).is_err());
+        assert!(load_with(&["--prefix", "!"]).is_ok());
+    }
+
+    #[test]
+    fn voteskip_percent_must_be_between_1_and_100() {
+        assert!(load_with(&["--voteskip-percent", "0"]).is_err());
+        assert!(load_with(&["--voteskip-percent", "1"]).is_ok());
+        assert!(load_with(&["--voteskip-percent", "100"]).is_ok());
+        assert!(load_with(&["--voteskip-percent", "101"]).is_err());
+    }
+
+    #[test]
+    fn zero_limits_are_rejected_where_they_make_no_sense() {
+        assert!(load_with(&["--max-playlist-tracks", "0"]).is_err());
+        assert!(load_with(&["--search-timeout", "0"]).is_err());
+        assert!(load_with(&["--max-queue-length", "0"]).is_ok());
+        assert!(load_with(&["--max-track-duration", "0"]).is_ok());
+    }
+
+    #[test]
+    fn invalid_log_levels_and_music_dirs_are_rejected() {
+        assert!(load_with(&["--log-level", "rybot2=loud"]).is_err());
+        assert!(load_with(&["--music-dir", "/nonexistent/rybot2"]).is_err());
+    }
+
+    #[test]
+    fn flags_override_the_config_file() {
+        let path = write_file("override", "prefix = \"?\"\nvoteskip_percent = 75\ngroups = [\"Voice\"]\n");
+        let config = load_with(&["--config", path.to_str().unwrap(), "--prefix", "!"]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.prefix, "!");
+        assert_eq!(config.voteskip_percent, 75);
+        assert!(config.group_enabled("voice"));
+        assert!(!config.group_enabled("effects"));
+    }
+
+    #[test]
+    fn unknown_config_file_fields_are_rejected() {
+        let path = write_file("unknown", "prefixx = \"!\"\n");
+        let result = load_with(&["--config", path.to_str().unwrap()]);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+}
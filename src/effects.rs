@@ -0,0 +1,207 @@
+use image::{
+    codecs::jpeg::{JpegDecoder, JpegEncoder},
+    imageops::{self, FilterType},
+    ColorType, DynamicImage, Rgba, RgbaImage,
+};
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group},
+    },
+    model::channel::Message,
+};
+
+use crate::{images, send_image, send_msg};
+
+#[group]
+#[commands(grayscale, sepia, blur, pixelate, rotate, flip, hue, deepfry)]
+pub struct Effects;
+
+// loads the image the command targets, applies an effect to every frame of it and sends the result
+pub async fn apply_effect<F>(ctx: &Context, msg: &Message, target: &str, effect: F) -> CommandResult
+where
+    F: Fn(&mut RgbaImage) + Send + 'static,
+{
+    let _ = msg.channel_id.broadcast_typing(&ctx.http).await;
+
+    let frames = match images::resolve_image_url(ctx, msg, target).await {
+        Ok(url) => images::load_image(&url).await,
+        Err(reason) => Err(reason),
+    };
+    let frames = match frames {
+        Ok(frames) => frames,
+        Err(reason) => {
+            println!("Error loading image: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error loading image: {}", reason)).await;
+            return Ok(());
+        },
+    };
+
+    match images::transform(frames, effect).await {
+        Ok(image) => send_image(ctx, msg, "image", &image).await,
+        Err(reason) => {
+            println!("Error processing image: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error processing image: {}", reason)).await;
+        },
+    }
+
+    Ok(())
+}
+
+// parses an optional leading number, leaving the rest of the arguments as the target
+fn number_arg<T: std::str::FromStr>(args: &mut Args, default: T) -> T {
+    match args.parse::<T>() {
+        Ok(value) => {
+            args.advance();
+            value
+        },
+        Err(_) => default,
+    }
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+// removes the colors of an image, keeping its transparency
+#[command]
+#[aliases(greyscale, gray, grey)]
+async fn grayscale(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    apply_effect(ctx, msg, args.rest(), |image| {
+        for pixel in image.pixels_mut() {
+            let value = luma(pixel) as u8;
+            *pixel = Rgba([value, value, value, pixel[3]]);
+        }
+    }).await
+}
+
+// gives an image the brown tint of old photos
+#[command]
+async fn sepia(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    apply_effect(ctx, msg, args.rest(), |image| {
+        for pixel in image.pixels_mut() {
+            let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+            *pixel = Rgba([
+                (0.393 * r + 0.769 * g + 0.189 * b).min(255.0) as u8,
+                (0.349 * r + 0.686 * g + 0.168 * b).min(255.0) as u8,
+                (0.272 * r + 0.534 * g + 0.131 * b).min(255.0) as u8,
+                pixel[3],
+            ]);
+        }
+    }).await
+}
+
+// blurs an image, more with a larger sigma
+#[command]
+async fn blur(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let sigma = number_arg(&mut args, 4.0_f32);
+    if !(0.1..=50.0).contains(&sigma) {
+        send_msg(ctx, msg, "Usage: `blur [sigma] [target]` (sigma between 0.1 and 50)").await;
+        return Ok(());
+    }
+
+    apply_effect(ctx, msg, args.rest(), move |image| *image = imageops::blur(image, sigma)).await
+}
+
+// turns an image into blocks of the specified size in pixels
+#[command]
+async fn pixelate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let block_size = number_arg(&mut args, 8_u32);
+    if !(2..=128).contains(&block_size) {
+        send_msg(ctx, msg, "Usage: `pixelate [size] [target]` (size between 2 and 128)").await;
+        return Ok(());
+    }
+
+    apply_effect(ctx, msg, args.rest(), move |image| {
+        let (width, height) = image.dimensions();
+        let small = imageops::resize(image, (width / block_size).max(1), (height / block_size).max(1), FilterType::Triangle);
+        *image = imageops::resize(&small, width, height, FilterType::Nearest);
+    }).await
+}
+
+// rotates an image clockwise by 90, 180 or 270 degrees
+#[command]
+async fn rotate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let degrees = number_arg(&mut args, 90_u32);
+    if ![90, 180, 270].contains(&degrees) {
+        send_msg(ctx, msg, "Usage: `rotate [90|180|270] [target]`").await;
+        return Ok(());
+    }
+
+    apply_effect(ctx, msg, args.rest(), move |image| match degrees {
+        90 => *image = imageops::rotate90(image),
+        180 => imageops::rotate180_in_place(image),
+        _ => *image = imageops::rotate270(image),
+    }).await
+}
+
+// mirrors an image horizontally, or vertically
+#[command]
+async fn flip(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let vertical = match args.current() {
+        Some("vertical") | Some("v") => {
+            args.advance();
+            true
+        },
+        Some("horizontal") | Some("h") => {
+            args.advance();
+            false
+        },
+        _ => false,
+    };
+
+    apply_effect(ctx, msg, args.rest(), move |image| {
+        if vertical {
+            imageops::flip_vertical_in_place(image);
+        } else {
+            imageops::flip_horizontal_in_place(image);
+        }
+    }).await
+}
+
+// shifts the colors of an image around the color wheel by the specified degrees
+#[command]
+async fn hue(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let degrees = match args.parse::<i32>() {
+        Ok(degrees) => {
+            args.advance();
+            degrees
+        },
+        Err(_) => {
+            send_msg(ctx, msg, "Usage: `hue <degrees> [target]`").await;
+            return Ok(());
+        },
+    };
+
+    apply_effect(ctx, msg, args.rest(), move |image| *image = imageops::huerotate(image, degrees)).await
+}
+
+// oversaturates, sharpens and compresses an image until it looks deep fried
+#[command]
+async fn deepfry(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    apply_effect(ctx, msg, args.rest(), |image| {
+        for pixel in image.pixels_mut() {
+            let value = luma(pixel);
+            for channel in 0..3 {
+                pixel[channel] = (value + (pixel[channel] as f32 - value) * 2.5).clamp(0.0, 255.0) as u8;
+            }
+        }
+        *image = imageops::contrast(image, 40.0);
+        *image = imageops::unsharpen(image, 2.0, 5);
+
+        // a low quality JPEG round trip adds the artifacts, the alpha channel is restored afterwards
+        let rgb = DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+        let mut content = Vec::new();
+        if JpegEncoder::new_with_quality(&mut content, 8).encode(&rgb, rgb.width(), rgb.height(), ColorType::Rgb8).is_err() {
+            return;
+        }
+        let compressed = match JpegDecoder::new(content.as_slice()).and_then(DynamicImage::from_decoder) {
+            Ok(compressed) => compressed.into_rgb8(),
+            Err(_) => return,
+        };
+        for (pixel, compressed_pixel) in image.pixels_mut().zip(compressed.pixels()) {
+            *pixel = Rgba([compressed_pixel[0], compressed_pixel[1], compressed_pixel[2], pixel[3]]);
+        }
+    }).await
+}
@@ -0,0 +1,513 @@
+use image::{
+    codecs::{gif::{GifDecoder, GifEncoder, Repeat}, png::PngEncoder},
+    imageops::{self, FilterType},
+    io::Reader,
+    AnimationDecoder, ColorType, Delay, Frame, ImageDecoder, ImageFormat, RgbaImage,
+};
+use libwebp_sys::{
+    WebPAnimDecoder, WebPAnimDecoderDelete, WebPAnimDecoderGetInfo, WebPAnimDecoderGetNext, WebPAnimDecoderHasMoreFrames,
+    WebPAnimDecoderNew, WebPAnimDecoderOptions, WebPAnimDecoderOptionsInit, WebPAnimInfo, WebPData, WebPGetInfo, MODE_RGBA,
+};
+use reqwest::{header::LOCATION, redirect::Policy, Url};
+use serenity::{
+    client::Context,
+    model::channel::{Attachment, Message},
+    utils::{parse_emoji, parse_username},
+};
+use std::{
+    io::Cursor,
+    mem::MaybeUninit,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    os::raw::c_int,
+    ptr, slice,
+};
+
+use crate::{Result, ResultExt};
+
+const IMAGE_EXTENSIONS: &[&str] = &["gif", "jpeg", "jpg", "png", "webp"];
+// the largest file a bot can upload to a server without boosts
+const MAX_UPLOAD_SIZE: usize = 8 * 1024 * 1024;
+const MAX_DOWNLOAD_SIZE: usize = 16 * 1024 * 1024;
+// redirects are followed one at a time, so every address they lead to can be checked
+const MAX_REDIRECTS: usize = 5;
+// how many messages `last` looks through for an image
+const LAST_IMAGE_SEARCH_LIMIT: u64 = 50;
+// larger images are scaled down before being processed
+const MAX_IMAGE_SIZE: u32 = 2048;
+// frames are decoded at full size before being scaled down, so larger images are rejected from their header
+const MAX_DECODED_PIXELS: u64 = 50_000_000;
+// animations are scaled down further while they're decoded, since every frame is kept in memory
+const MAX_ANIMATION_SIZE: u32 = 512;
+const MAX_ANIMATION_PIXELS: u64 = 64 * 1024 * 1024;
+// animations aren't scaled down any further than this to fit in an upload, frames are dropped instead
+const MIN_ANIMATION_SIZE: u32 = 128;
+// how much of the upload limit a shrunk image aims for, since its encoded size can only be estimated
+const SIZE_ESTIMATE_MARGIN: f64 = 0.85;
+// 1 is the best quality and 30 the fastest, animated avatars have a lot of frames to quantize
+const GIF_ENCODER_SPEED: i32 = 10;
+
+// an image ready to be uploaded
+pub struct EncodedImage {
+    pub content: Vec<u8>,
+    // `png` for still images and `gif` for animated ones
+    pub extension: &'static str,
+}
+
+fn is_image(attachment: &Attachment) -> bool {
+    let image_content_type = attachment.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("image/"));
+    let image_extension = attachment.filename.rsplit_once('.')
+        .is_some_and(|(_, extension)| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+    image_content_type || image_extension
+}
+
+// the first image attached to or embedded in a message
+fn message_image(msg: &Message) -> Option<String> {
+    if let Some(attachment) = msg.attachments.iter().find(|attachment| is_image(attachment)) {
+        return Some(attachment.url.clone());
+    }
+
+    msg.embeds.iter().find_map(|embed| {
+        embed.image.as_ref().map(|image| image.url.clone())
+            .or_else(|| embed.thumbnail.as_ref().map(|thumbnail| thumbnail.url.clone()))
+    })
+}
+
+// finds the image a command works on, in order: an attached image, the image in the replied-to message,
+// `last` for the last image posted in the channel, an image URL, a custom emoji or a user mention in `target`,
+// or else the author's avatar
+pub async fn resolve_image_url(ctx: &Context, msg: &Message, target: &str) -> Result<String> {
+    if let Some(url) = msg.attachments.iter().find(|attachment| is_image(attachment)).map(|attachment| attachment.url.clone()) {
+        return Ok(url);
+    }
+    let target = target.trim();
+    if target.is_empty() {
+        if let Some(url) = msg.referenced_message.as_deref().and_then(message_image) {
+            return Ok(url);
+        }
+    }
+
+    if target == "last" {
+        let messages = msg.channel_id.messages(&ctx.http, |retriever| retriever.before(msg.id).limit(LAST_IMAGE_SEARCH_LIMIT)).await?;
+        return messages.iter().find_map(message_image)
+            .ok_or_else(|| format!("No image was posted in the last {} messages", LAST_IMAGE_SEARCH_LIMIT).into());
+    }
+
+    // links wrapped in `<>` don't get an embed, so they're accepted too
+    let url = target.trim_start_matches('<').trim_end_matches('>');
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return Ok(url.to_string());
+    }
+    if let Some(emoji) = parse_emoji(target) {
+        return Ok(emoji.url());
+    }
+    // mentions are read from the arguments, since replies also mention the author of the replied-to message
+    if let Some(user_id) = parse_username(target) {
+        let user = match msg.mentions.iter().find(|user| user.id.0 == user_id) {
+            Some(user) => user.clone(),
+            None => ctx.http.get_user(user_id).await?,
+        };
+        return Ok(user.face());
+    }
+    if !target.is_empty() {
+        return Err(format!("`{}` isn't an image URL, a custom emoji, a mention or `last`", target).into());
+    }
+
+    Ok(msg.author.face())
+}
+
+// downloads an image, such as an avatar, and decodes every frame of it
+pub async fn load_image(url: &str) -> Result<Vec<Frame>> {
+    let content = download(url).await?;
+
+    tokio::task::spawn_blocking(move || decode_image(&content)).await.chain_err(|| "Image decoding was interrupted")?
+}
+
+// downloads from public addresses only, so image URLs can't be used to reach the bot's own network
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let mut url = Url::parse(url).chain_err(|| format!("`{}` isn't a valid URL", url))?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let addresses = public_addresses(&url).await?;
+        // the request goes to the checked addresses, so the host can't resolve somewhere else in the meantime
+        let mut client = reqwest::Client::builder().redirect(Policy::none());
+        if let Some(domain) = url.domain() {
+            client = client.resolve_to_addrs(domain, &addresses);
+        }
+        let mut response = client.build()?.get(url.clone()).send().await?;
+
+        if response.status().is_redirection() {
+            let location = response.headers().get(LOCATION).and_then(|location| location.to_str().ok())
+                .ok_or("The image URL redirects without a location")?;
+            url = url.join(location).chain_err(|| format!("The image URL redirects to an invalid URL `{}`", location))?;
+            continue;
+        }
+        response = response.error_for_status()?;
+
+        // the size is checked while downloading, since `Content-Length` can be missing or wrong
+        let too_large = || format!("Images can be at most {} MB", MAX_DOWNLOAD_SIZE / 1024 / 1024).into();
+        if response.content_length().is_some_and(|content_length| content_length as usize > MAX_DOWNLOAD_SIZE) {
+            return Err(too_large());
+        }
+        let mut content = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            content.extend_from_slice(&chunk);
+            if content.len() > MAX_DOWNLOAD_SIZE {
+                return Err(too_large());
+            }
+        }
+        return Ok(content);
+    }
+
+    Err(format!("The image URL redirects more than {} times", MAX_REDIRECTS).into())
+}
+
+// resolves the host of a URL, failing unless every address it resolves to is public
+async fn public_addresses(url: &Url) -> Result<Vec<SocketAddr>> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("`{}` isn't an HTTP URL", url).into());
+    }
+    let host = url.host_str().ok_or_else(|| format!("`{}` doesn't have a host", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    // IPv6 hosts are written in brackets
+    let addresses: Vec<SocketAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port)).await?.collect(),
+    };
+    if addresses.is_empty() || !addresses.iter().all(|address| is_public(address.ip())) {
+        return Err(format!("Images can only be downloaded from public addresses, `{}` isn't one", host).into());
+    }
+
+    Ok(addresses)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                // includes cloud metadata endpoints like 169.254.169.254
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // "this network", carrier-grade NAT, benchmarking and reserved ranges
+                || first == 0
+                || (first == 100 && (64..128).contains(&second))
+                || (first == 198 && (second == 18 || second == 19))
+                || first >= 240)
+        },
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            let octets = ip.octets();
+            // IPv4 addresses can be written as IPv6 ones too, including ::1, which maps to 0.0.0.1
+            if let Some(ipv4) = ip.to_ipv4() {
+                return is_public(IpAddr::V4(ipv4));
+            }
+            // NAT64 addresses embed an IPv4 address at the end, 6to4 addresses right after their prefix
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                return is_public(IpAddr::V4(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15])));
+            }
+            if segments[0] == 0x2002 {
+                return is_public(IpAddr::V4(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])));
+            }
+            !(ip.is_multicast()
+                // Teredo addresses can tunnel to any IPv4 address, and local-use NAT64 addresses to any network
+                || (segments[0] == 0x2001 && segments[1] == 0)
+                || (segments[0] == 0x64 && segments[1] == 0xff9b && segments[2] == 1)
+                // unique local and link-local addresses
+                || segments[0] & 0xfe00 == 0xfc00
+                || segments[0] & 0xffc0 == 0xfe80
+                // documentation addresses
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        },
+    }
+}
+
+// decodes a WebP, PNG, JPEG or GIF image into RGBA frames, still images have a single frame
+pub fn decode_image(content: &[u8]) -> Result<Vec<Frame>> {
+    // the format is sniffed from the content, since URLs don't always end with the right extension
+    let frames = match image::guess_format(content)? {
+        ImageFormat::WebP => decode_webp(content)?,
+        ImageFormat::Gif => {
+            let decoder = GifDecoder::new(Cursor::new(content))?;
+            check_dimensions(decoder.dimensions())?;
+            let mut frames = DecodedFrames::default();
+            for frame in decoder.into_frames() {
+                frames.push(frame?)?;
+            }
+            frames.0
+        },
+        format @ (ImageFormat::Png | ImageFormat::Jpeg) => {
+            check_dimensions(Reader::with_format(Cursor::new(content), format).into_dimensions()?)?;
+            let image = image::load_from_memory_with_format(content, format)?.into_rgba8();
+            let mut frames = DecodedFrames::default();
+            frames.push(Frame::new(image))?;
+            frames.0
+        },
+        format => return Err(format!("Unsupported image format {:?}", format).into()),
+    };
+
+    if frames.is_empty() {
+        return Err("The image doesn't contain any frames".into());
+    }
+
+    Ok(frames)
+}
+
+fn check_dimensions((width, height): (u32, u32)) -> Result<()> {
+    if width as u64 * height as u64 > MAX_DECODED_PIXELS {
+        return Err(format!("Images can be at most {} megapixels, this one is {}x{}", MAX_DECODED_PIXELS / 1_000_000, width, height).into());
+    }
+
+    Ok(())
+}
+
+// collects frames as they're decoded, scaling each one down right away
+#[derive(Default)]
+struct DecodedFrames(Vec<Frame>);
+
+impl DecodedFrames {
+    fn push(&mut self, frame: Frame) -> Result<()> {
+        // a second frame makes the image an animation, so the first frame is scaled down to match
+        if self.0.len() == 1 {
+            let first_frame = self.0.remove(0);
+            self.0.push(fit_frame(first_frame, MAX_ANIMATION_SIZE));
+        }
+        let max_size = if self.0.is_empty() { MAX_IMAGE_SIZE } else { MAX_ANIMATION_SIZE };
+        let frame = fit_frame(frame, max_size);
+
+        // every frame of an animation covers the whole canvas, so they're all the same size
+        let (width, height) = frame.buffer().dimensions();
+        if (self.0.len() as u64 + 1) * width as u64 * height as u64 > MAX_ANIMATION_PIXELS {
+            return Err(format!("The animation has more than {} frames", self.0.len()).into());
+        }
+        self.0.push(frame);
+
+        Ok(())
+    }
+}
+
+// applies an effect to every frame of an image and encodes the result, on a blocking thread since encoding GIFs is slow
+pub async fn transform<F>(mut frames: Vec<Frame>, effect: F) -> Result<EncodedImage>
+where
+    F: Fn(&mut RgbaImage) + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        for frame in &mut frames {
+            effect(frame.buffer_mut());
+        }
+        encode_image(frames)
+    }).await.chain_err(|| "Image processing was interrupted")?
+}
+
+// encodes still images as PNG and animations as GIF, shrinking them until they fit in an upload
+pub fn encode_image(mut frames: Vec<Frame>) -> Result<EncodedImage> {
+    let animated = frames.len() > 1;
+    let extension = if animated { "gif" } else { "png" };
+    // still images can always be scaled down further, animations lose frames instead once they're small
+    let min_size = if animated { MIN_ANIMATION_SIZE } else { 1 };
+
+    loop {
+        let content = if animated { encode_gif(&frames)? } else { encode_png(frames[0].buffer())? };
+        if content.len() <= MAX_UPLOAD_SIZE {
+            return Ok(EncodedImage { content, extension });
+        }
+
+        // the encoded size roughly follows the number of pixels, so the size that fits is estimated from this attempt
+        // instead of shrinking a step at a time, which would take several slow encodes
+        let oversize = content.len() as f64 / MAX_UPLOAD_SIZE as f64;
+        let (width, height) = frames[0].buffer().dimensions();
+        if width.max(height) > min_size {
+            let scale = (SIZE_ESTIMATE_MARGIN / oversize).sqrt().max(min_size as f64 / width.max(height) as f64);
+            frames = frames.into_iter().map(|frame| scale_frame(frame, scale)).collect();
+        } else if frames.len() > 1 {
+            let keep_every = ((oversize / SIZE_ESTIMATE_MARGIN).ceil() as usize).max(2);
+            frames = drop_frames(frames, keep_every);
+        } else {
+            return Err("The image is too large to upload".into());
+        }
+    }
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    PngEncoder::new(&mut content).encode(image, image.width(), image.height(), ColorType::Rgba8)?;
+
+    Ok(content)
+}
+
+fn encode_gif(frames: &[Frame]) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut content, GIF_ENCODER_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames.iter().cloned())?;
+    }
+
+    Ok(content)
+}
+
+fn fit_frame(frame: Frame, max_size: u32) -> Frame {
+    let (width, height) = frame.buffer().dimensions();
+    if width.max(height) > max_size {
+        return scale_frame(frame, max_size as f64 / width.max(height) as f64);
+    }
+
+    frame
+}
+
+fn scale_frame(frame: Frame, scale: f64) -> Frame {
+    let delay = frame.delay();
+    let image = frame.into_buffer();
+    let width = ((image.width() as f64 * scale) as u32).max(1);
+    let height = ((image.height() as f64 * scale) as u32).max(1);
+    Frame::from_parts(imageops::resize(&image, width, height, FilterType::Triangle), 0, 0, delay)
+}
+
+// keeps one of every `keep_every` frames, showing the kept frames for longer so the animation keeps its speed
+fn drop_frames(frames: Vec<Frame>, keep_every: usize) -> Vec<Frame> {
+    let mut kept_frames: Vec<Frame> = Vec::new();
+    for (index, frame) in frames.into_iter().enumerate() {
+        if index % keep_every == 0 {
+            kept_frames.push(frame);
+        } else if let Some(kept_frame) = kept_frames.pop() {
+            let delay = Delay::from_numer_denom_ms(delay_ms(kept_frame.delay()) + delay_ms(frame.delay()), 1);
+            kept_frames.push(Frame::from_parts(kept_frame.into_buffer(), 0, 0, delay));
+        }
+    }
+
+    kept_frames
+}
+
+fn delay_ms(delay: Delay) -> u32 {
+    let (numerator, denominator) = delay.numer_denom_ms();
+    numerator / denominator.max(1)
+}
+
+// frees libwebp's animation decoder once decoding is done, including on errors
+struct AnimDecoder(*mut WebPAnimDecoder);
+
+impl Drop for AnimDecoder {
+    fn drop(&mut self) {
+        unsafe { WebPAnimDecoderDelete(self.0) }
+    }
+}
+
+// decodes still and animated WebP images alike through libwebp's animation decoder
+fn decode_webp(content: &[u8]) -> Result<Vec<Frame>> {
+    let data = WebPData { bytes: content.as_ptr(), size: content.len() };
+
+    // the header holds the canvas size, which every frame is decoded at
+    let (mut width, mut height): (c_int, c_int) = (0, 0);
+    if unsafe { WebPGetInfo(content.as_ptr(), content.len(), &mut width, &mut height) } == 0 {
+        return Err("Unable to decode the WebP image".into());
+    }
+    check_dimensions((width as u32, height as u32))?;
+
+    let (decoder, info) = unsafe {
+        let mut options = MaybeUninit::<WebPAnimDecoderOptions>::uninit();
+        if WebPAnimDecoderOptionsInit(options.as_mut_ptr()) == 0 {
+            return Err("Unable to initialize the WebP decoder".into());
+        }
+        let mut options = options.assume_init();
+        options.color_mode = MODE_RGBA;
+
+        let decoder = WebPAnimDecoderNew(&data, &options);
+        if decoder.is_null() {
+            return Err("Unable to decode the WebP image".into());
+        }
+        let decoder = AnimDecoder(decoder);
+
+        let mut info = MaybeUninit::<WebPAnimInfo>::uninit();
+        if WebPAnimDecoderGetInfo(decoder.0, info.as_mut_ptr()) == 0 {
+            return Err("Unable to decode the WebP image".into());
+        }
+        (decoder, info.assume_init())
+    };
+    let frame_length = info.canvas_width as usize * info.canvas_height as usize * 4;
+
+    // every frame covers the whole canvas, and timestamps are when each frame ends in milliseconds
+    let mut frames = DecodedFrames::default();
+    let mut previous_timestamp = 0;
+    while unsafe { WebPAnimDecoderHasMoreFrames(decoder.0) } != 0 {
+        let mut buffer: *mut u8 = ptr::null_mut();
+        let mut timestamp: c_int = 0;
+        // the buffer belongs to the decoder and is only valid until the next frame is decoded
+        let pixels = unsafe {
+            if WebPAnimDecoderGetNext(decoder.0, &mut buffer, &mut timestamp) == 0 {
+                return Err("Unable to decode a frame of the WebP image".into());
+            }
+            slice::from_raw_parts(buffer, frame_length).to_vec()
+        };
+
+        let image = RgbaImage::from_raw(info.canvas_width, info.canvas_height, pixels)
+            .ok_or("Unable to decode a frame of the WebP image")?;
+        let delay = Delay::from_numer_denom_ms((timestamp - previous_timestamp).max(0) as u32, 1);
+        frames.push(Frame::from_parts(image, 0, 0, delay))?;
+        previous_timestamp = timestamp;
+    }
+
+    Ok(frames.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(delays: &[u32]) -> Vec<Frame> {
+        delays.iter().map(|delay| Frame::from_parts(RgbaImage::new(1, 1), 0, 0, Delay::from_numer_denom_ms(*delay, 1))).collect()
+    }
+
+    #[test]
+    fn dropped_frames_keep_the_animation_length() {
+        let kept = drop_frames(frames(&[10, 20, 30, 40, 50]), 2);
+        assert_eq!(kept.iter().map(|frame| delay_ms(frame.delay())).collect::<Vec<_>>(), vec![30, 70, 50]);
+
+        let kept = drop_frames(frames(&[10, 20, 30, 40, 50, 60, 70]), 3);
+        assert_eq!(kept.iter().map(|frame| delay_ms(frame.delay())).collect::<Vec<_>>(), vec![60, 150, 70]);
+    }
+
+    #[test]
+    fn small_images_are_encoded_as_is() {
+        let image = encode_image(frames(&[10])).unwrap();
+        assert_eq!(image.extension, "png");
+        let animation = encode_image(frames(&[10, 20])).unwrap();
+        assert_eq!(animation.extension, "gif");
+        assert_eq!(decode_image(&animation.content).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn frames_are_scaled_down_while_decoding() {
+        let mut image = DecodedFrames::default();
+        image.push(Frame::new(RgbaImage::new(4096, 1024))).unwrap();
+        assert_eq!(image.0[0].buffer().dimensions(), (2048, 512));
+
+        let mut animation = DecodedFrames::default();
+        animation.push(Frame::new(RgbaImage::new(1024, 1024))).unwrap();
+        animation.push(Frame::new(RgbaImage::new(1024, 1024))).unwrap();
+        assert!(animation.0.iter().all(|frame| frame.buffer().dimensions() == (512, 512)));
+    }
+
+    #[test]
+    fn huge_images_are_rejected_before_decoding() {
+        assert!(check_dimensions((4096, 4096)).is_ok());
+        assert!(check_dimensions((65535, 65535)).is_err());
+    }
+
+    #[test]
+    fn only_public_addresses_are_downloaded_from() {
+        for ip in ["1.1.1.1", "162.159.128.233", "2606:4700::6810:85e5", "64:ff9b::101:101", "2002:101:101::1"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1", "10.0.0.1", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0", "100.64.0.1", "255.255.255.255",
+            "::1", "::", "::ffff:127.0.0.1", "64:ff9b::a9fe:a9fe", "64:ff9b:1::1", "2002:7f00:1::", "2002:a9fe:a9fe::1",
+            "2001:0:4136:e378:8000:63bf:3fff:fdd2", "fd00::1", "fe80::1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
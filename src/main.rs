@@ -1,28 +1,41 @@
+mod admin;
+mod config;
+mod effects;
+mod images;
+mod reactions;
+mod settings;
+mod storage;
+mod voice;
+
 use error_chain::error_chain;
-use libwebp::WebPDecodeRGB;
-use std::{env, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
+use std::{borrow::Cow, collections::HashSet, process, sync::Arc};
 use serenity::{
     async_trait,
     client::Context,
     client::{Client, EventHandler, bridge::gateway::ShardManager},
     framework::standard::{
-        Args, CommandResult,
-        Delimiter, StandardFramework,
+        Args, CommandGroup, CommandResult,
+        Delimiter, DispatchError, Reason, StandardFramework,
         macros::{command, group, hook},
     },
-    http::Http,
+    http::{AttachmentType, Http},
     model::{
         channel::{Message, ReactionType},
         gateway::{Activity, Ready},
-        misc::Mentionable,
-        prelude::{ChannelId, GuildId},
+        guild::Guild,
+        id::{ChannelId, GuildId},
+        voice::VoiceState,
     },
     utils::{content_safe, ContentSafeOptions},
     prelude::*,
 };
-use image::{ImageBuffer, RgbImage, imageops};
-use songbird::{Event, EventContext, EventHandler as VoiceEventHandler, SerenityInit, Songbird};
-use tempfile::Builder;
+use image::{Frame, ImageBuffer, imageops};
+use songbird::SerenityInit;
+use tracing_subscriber::EnvFilter;
+
+use config::{Config, ConfigContainer};
+use settings::{Settings, SettingsContainer, SETTINGS_FILE_NAME};
+use storage::JsonFileStorage;
 
 struct ShardManagerContainer;
 impl TypeMapKey for ShardManagerContainer {
@@ -33,41 +46,61 @@ error_chain! {
     foreign_links {
         Io(std::io::Error);
         HttpRequest(reqwest::Error);
+        Image(image::ImageError);
+        Json(serde_json::Error);
+        Serenity(serenity::Error);
     }
 }
 
 struct Handler;
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, _ctx: Context, ready: Ready) {
         println!("{} is connected!", ready.user.name);
     }
+
+    // guilds the bot was already in arrive one by one at startup, so they're saved together once the cache is ready
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        settings::save(&ctx).await;
+        // sessions are only resumed once it's known who is in the voice channels
+        voice::offer_session_restore(&ctx).await;
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: Guild, is_new: bool) {
+        if reactions::seed_vent_channels(&ctx, &guild).await && is_new {
+            settings::save(&ctx).await;
+        }
+    }
+
+    async fn voice_state_update(&self, ctx: Context, guild_id: Option<GuildId>, _old: Option<VoiceState>, _new: VoiceState) {
+        if let Some(guild_id) = guild_id {
+            voice::leave_if_alone(&ctx, guild_id).await;
+        }
+    }
 }
 
 #[group]
-#[commands(help, activity, say, boop, dm, pfp, invert, color, ping, join, leave, play, skip, stop, np)]
+#[commands(help, activity, say, boop, dm, pfp, invert, color, ping)]
 struct General;
 
+// every command group, in the order they are registered with the framework
+static COMMAND_GROUPS: &[&CommandGroup] = &[&GENERAL_GROUP, &effects::EFFECTS_GROUP, &voice::VOICE_GROUP, &admin::ADMIN_GROUP, &reactions::REACTIONS_GROUP];
+
 #[hook]
 async fn normal_message(ctx: &Context, msg: &Message) {
-    // TODO: this method of disabling reactions in vent channels is hacky
-    //       a better method would be to allow users to configure this on a per-channnel basis
-    //       using commands that would write to some kind of external config file.
-    let message_channel = msg.channel_id.name(&ctx.cache).await;
-    if message_channel != Some("vent".to_string()) {
-        let message_string = msg.content.to_lowercase().split_whitespace().collect::<String>();
-        if message_string.contains("fox") || message_string.contains("focks") {
-            //println!("{} found a fox OwO", msg.author.name);
-            react_msg(ctx, msg, ReactionType::Unicode("🦊".to_string())).await;
-        }
-        if message_string.contains("cat") {
-            //println!("{} found a stinky cat :(", msg.author.name);
-            react_msg(ctx, msg, ReactionType::Unicode("🐱".to_string())).await;
-        }
-        if message_string.contains("lemon") {
-            //println!("{} found a sour lemon", msg.author.name);
-            react_msg(ctx, msg, ReactionType::Unicode("🍋".to_string())).await;
-        }
+    reactions::react_to_keywords(ctx, msg).await;
+}
+
+#[hook]
+async fn dispatch_error(ctx: &Context, msg: &Message, error: DispatchError) {
+    match error {
+        DispatchError::LackingPermissions(permissions) => {
+            let permission_names = permissions.get_permission_names().join(", ");
+            send_msg(ctx, msg, &format!("You need the following permissions to use this command: {}", permission_names)).await;
+        },
+        DispatchError::OnlyForGuilds => send_msg(ctx, msg, "This command can only be used in a server").await,
+        DispatchError::CheckFailed(_, Reason::User(reason)) => send_msg(ctx, msg, &reason).await,
+        _ => println!("Error dispatching command: {:?}", error),
     }
 }
 
@@ -77,15 +110,36 @@ async fn send_msg(ctx: &Context, msg: &Message, content: &str) {
     }
 }
 
+async fn send_channel_msg(http: &Http, channel_id: ChannelId, content: &str) {
+    if let Err(reason) = channel_id.say(http, content).await {
+        println!("Error sending message: {:?}", reason);
+    }
+}
+
 async fn react_msg(ctx: &Context, msg: &Message, reaction: ReactionType) {
     if let Err(reason) = msg.react(&ctx.http, reaction).await {
         println!("Error reacting to message: {:?}", reason);
     }
 }
 
+// cuts text down to `max_length` characters for listings that have to stay below Discord's message length limit
+fn shorten(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+    let mut shortened: String = text.chars().take(max_length - 1).collect();
+    shortened.push('…');
+    shortened
+}
+
 
-async fn send_file(ctx: &Context, msg: &Message, path: Vec<&str>) {
-    if let Err(reason) = msg.channel_id.send_files(&ctx.http, path, |m| {
+// uploads a processed image from memory, named after its format
+async fn send_image(ctx: &Context, msg: &Message, name: &str, image: &images::EncodedImage) {
+    let attachment = AttachmentType::Bytes {
+        data: Cow::from(image.content.as_slice()),
+        filename: format!("{}.{}", name, image.extension),
+    };
+    if let Err(reason) = msg.channel_id.send_files(&ctx.http, vec![attachment], |m| {
         m.content("")
     }).await {
         println!("Error sending file: {:?}", reason);
@@ -94,32 +148,86 @@ async fn send_file(ctx: &Context, msg: &Message, path: Vec<&str>) {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    if let Err(reason) = run().await {
+        eprintln!("Error: {}", reason);
+        for cause in reason.iter().skip(1) {
+            eprintln!("Caused by: {}", cause);
+        }
+        process::exit(1);
+    }
+}
+
+async fn run() -> Result<()> {
+    let config = Config::load()?;
 
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
+    match &config.log_filter {
+        Some(log_filter) => tracing_subscriber::fmt().with_env_filter(EnvFilter::new(log_filter)).init(),
+        None => tracing_subscriber::fmt::init(),
+    }
+
+    for group in &config.groups {
+        if !COMMAND_GROUPS.iter().any(|command_group| command_group.name.eq_ignore_ascii_case(group)) {
+            let group_names = COMMAND_GROUPS.iter().map(|command_group| command_group.name.to_lowercase()).collect::<Vec<_>>();
+            return Err(format!("Unknown command group `{}` (valid groups: {})", group, group_names.join(", ")).into());
+        }
+    }
+
+    let settings = Settings::load(Box::new(JsonFileStorage::new(config.data_dir.join(SETTINGS_FILE_NAME))))
+        .chain_err(|| "Unable to load settings")?;
+    let sessions = voice::SessionStore::load(Box::new(JsonFileStorage::new(config.data_dir.join(voice::SESSIONS_FILE_NAME))));
+
+    let http = Http::new_with_token(&config.token);
+    let bot_id = http.get_current_user().await.chain_err(|| "Unable to get the bot user (is the token valid?)")?.id;
+
+    let owners = if config.owners.is_empty() {
+        let application_info = http.get_current_application_info().await.chain_err(|| "Unable to get the bot application info")?;
+        let mut owners = HashSet::new();
+        owners.insert(application_info.owner.id);
+        owners
+    } else {
+        config.owners.clone()
+    };
 
-    let framework = StandardFramework::new()
+    // the prefix is looked up per guild, so no static prefix is configured
+    let mut framework = StandardFramework::new()
         .configure(|c| c
             .with_whitespace(true)
-            .prefix("~"))
-        .normal_message(normal_message)
-        .group(&GENERAL_GROUP);
+            .on_mention(Some(bot_id))
+            .owners(owners)
+            .prefix("")
+            .dynamic_prefix(admin::dynamic_prefix))
+        .on_dispatch_error(dispatch_error);
+    for group in COMMAND_GROUPS {
+        if config.group_enabled(group.name) {
+            framework = framework.group(group);
+        }
+    }
+    if config.group_enabled(reactions::REACTIONS_GROUP.name) {
+        framework = framework.normal_message(normal_message);
+    }
 
-    let mut client = Client::builder(&token)
+    let mut client = Client::builder(&config.token)
         .event_handler(Handler)
         .framework(framework)
         .register_songbird()
         .await
-        .expect("Error creating client");
+        .chain_err(|| "Unable to create the client")?;
 
     {
         let mut data = client.data.write().await;
         data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
+        data.insert::<SettingsContainer>(Arc::new(RwLock::new(settings)));
+        data.insert::<ConfigContainer>(Arc::new(config));
+        data.insert::<voice::PlayerStateContainer>(Default::default());
+        data.insert::<voice::LibraryContainer>(Default::default());
+        data.insert::<voice::SessionContainer>(Arc::new(Mutex::new(sessions)));
     }
 
     if let Err(reason) = client.start().await {
         println!("Client error: {:?}", reason);
     }
+
+    Ok(())
 }
 
 #[command]
@@ -131,22 +239,62 @@ async fn help(ctx: &Context, msg: &Message) -> CommandResult {
     let audio_command_help_string = "audio playback commands:
     `join`: join the current voice channel
     `leave`: leave the current voice channel
-    `play`: queue/play the specified URL, or search YouTube and queue/play the first result
+    `play`: queue/play the specified URL or playlist, or search YouTube and queue/play the first result (plays attached audio files if nothing is specified)
+    `search <query>`: list the top YouTube results and pick one to queue/play
+    `library`/`lib` [page]/`library search <terms> [page:<n>]`: browse the music library (rescanned every 10 minutes or on `library refresh`), play a file with `play file:<name>`
+    `sb <name>`/`sb list`: play a soundboard clip over the current audio, or list the clips of this server
+    `sb add <name>`/`sb remove <name>`: save an attached audio file as a soundboard clip, or delete one
     `skip`: skip the currently playing audio in the queue
+    `voteskip`: vote to skip the currently playing audio, which is skipped once enough listeners vote
     `stop`: clear the audio queue
-    `np`: view current audio playback info\n\n";
+    `np`: view current audio playback info
+    `queue`/`q` [page]: list the tracks in the audio queue
+    `remove <position>`: remove a track from the audio queue
+    `move <from> <to>`: move a track to another position in the audio queue
+    `shuffle`: shuffle the audio queue
+    `clear`: remove every track from the audio queue except the current one
+    `pause`/`resume`: pause or resume the current audio track
+    `volume`/`vol` [0-200]: view or set the playback volume in percent
+    `seek <mm:ss>`: jump to a position in the current audio track
+    `filter bassboost|nightcore|vaporwave|normalize|off`: play the current audio track through an effect
+    `loop`/`repeat` [track|queue|off]: view or set whether the current track or the whole queue repeats";
     let misc_command_help_string = "misc commands:
     `help`: list valid commands and some system info
     `say`: print a message
     `boop`: boop another user :3
     `dm`: send a DM to a user
-    `pfp`: send the profile picture of a user (defaults to yourself if no username is mentioned)
-    `invert`: send the profile picture of a user with inverted colors (defaults to yourself if no username is mentioned)
+    `pfp`: send the profile picture of a user, or the link to any image the image commands work on
+    `invert`: invert the colors of an image, animated ones included (works like the image commands below)
     `color`/`colour`: send a 64x64 image of the specified hexadecimal color code (for example: `B28FEB`)";
+    let image_command_help_string = "image commands (work on an attached image, the image in the replied-to message, `last` for the last image in the channel, an image URL, a custom emoji, a mention or your own profile picture):
+    `grayscale`/`sepia`: remove the colors or give an old photo look
+    `blur [sigma]`/`pixelate [size]`: blur or pixelate (defaults to 4 and 8)
+    `rotate [90|180|270]`/`flip [horizontal|vertical]`: rotate clockwise or mirror
+    `hue <degrees>`: shift the colors around the color wheel
+    `deepfry`: deep fry";
+    let settings_command_help_string = "settings commands:
+    `idle`: show when the bot leaves the voice channel in this server
+    `idle set <minutes|never|default>`: change how long the bot waits without audio before leaving
+    `idle alone on|off`/`idle warn on|off`: leave as soon as everyone else leaves, warn a minute before leaving
+    `session`/`session restore`/`session discard`: show, resume or forget what was playing when the bot restarted
+    `session auto on|off`: resume what was playing after a restart without asking
+    `dj`: show the role needed to skip, stop and otherwise control playback in this server
+    `dj set <role>`/`dj reset`: only let a role (and server managers) control playback, or let everyone again
+    `prefix`: show the command prefix of this server (mentioning the bot instead of using the prefix works too)
+    `prefix set <prefix>`/`prefix reset`: change the command prefix of this server or restore the default
+    `reactions`: list the channels where keyword reactions are enabled or disabled
+    `reactions on`/`reactions off`: enable/disable keyword reactions in the current channel (add `guild` to apply to the whole server)
+    `reactions me on`/`reactions me off`: enable/disable keyword reactions on your own messages
+    `react` [page]: list the keyword reaction rules of this server
+    `react add <keyword> <emoji> [word|substring|regex]`: react with an emoji to messages containing a keyword (whole words only by default)
+    `react remove <keyword>`: remove the reaction rules for a keyword";
     help_string.push_str(audio_command_help_string);
-    help_string.push_str(misc_command_help_string);
 
-    send_msg(&ctx, &msg, &help_string).await;
+    // each section is sent separately to stay below Discord's message length limit
+    send_msg(ctx, msg, &help_string).await;
+    send_msg(ctx, msg, misc_command_help_string).await;
+    send_msg(ctx, msg, image_command_help_string).await;
+    send_msg(ctx, msg, settings_command_help_string).await;
     Ok(())
 }
 
@@ -155,259 +303,7 @@ async fn help(ctx: &Context, msg: &Message) -> CommandResult {
 async fn activity(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let activity = args.rest();
     ctx.set_activity(Activity::playing(activity)).await;
-    send_msg(&ctx, &msg, &format!("Activity set to \"Playing {}\"", activity)).await;
-    Ok(())
-}
-
-async fn join_impl(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let channel_id = guild.voice_states.get(&msg.author.id).and_then(|voice_state| voice_state.channel_id);
-
-    let connect_to = match channel_id {
-        Some(channel) => channel,
-        None => {
-            send_msg(&ctx, &msg, "Not in a voice channel").await;
-
-            return Ok(());
-        }
-    };
-
-    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
-
-    let (handle_lock, success) = manager.join(guild_id, connect_to).await;
-    if let Ok(_channel) = success {
-        send_msg(&ctx, &msg, &format!("Joined {}", connect_to.mention())).await;
-        let channel_id = msg.channel_id;
-        let send_http = ctx.http.clone();
-        let mut handle = handle_lock.lock().await;
-        handle.add_global_event(
-            Event::Periodic(Duration::from_secs(60), None),
-            ChannelDurationNotifier {
-                channel_id,
-                count: Default::default(),
-                http: send_http,
-                manager,
-                guild_id,
-            },
-        );
-    } else {
-        send_msg(&ctx, &msg, "Failed to join voice channel").await;
-    }
-
-    Ok(())
-}
-
-struct ChannelDurationNotifier {
-    channel_id: ChannelId,
-    count: Arc<AtomicUsize>,
-    http: Arc<Http>,
-
-    manager: Arc<Songbird>,
-    guild_id: GuildId,
-}
-
-// TODO: change `if let Some(handler_lock)` to `if let Some(handler)` and change `let handler = handler_lock.lock().await;` to `let handler_lock = handler.lock().await;`
-//       this should be changed throughout the entire file
-
-#[async_trait]
-impl VoiceEventHandler for ChannelDurationNotifier {
-    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
-        let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
-
-        if let Some(handler_lock) = self.manager.get(self.guild_id) {
-            let handler = handler_lock.lock().await;
-            if handler.queue().current().is_some() {
-                // the audio queue isn't empty, so set the number of minutes since last inactive to 0
-                self.count.store(0, Ordering::Relaxed);
-            }
-        }
-
-        if count == 5 {
-            // 5 minutes have passed since the audio queue last contained anything, so leave the VC
-            if let Err(reason) = self.manager.remove(self.guild_id).await {
-                self.channel_id.say(&self.http, &format!("Failed: {:?}", reason).as_str()).await.unwrap();
-            }
-            self.channel_id.say(&self.http, "Left the voice channel due to inactivity").await.unwrap();
-            self.count.store(0, Ordering::Relaxed);
-        }
-
-        None
-    }
-}
-
-// joins the voice channel that the requesting user is currently in
-#[command]
-#[only_in(guilds)]
-async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-    join_impl(ctx, msg).await
-}
-
-// leaves the current voice channel
-#[command]
-#[only_in(guilds)]
-async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
-    let has_handler = manager.get(guild_id).is_some();
-
-    if has_handler {
-        if let Err(reason) = manager.remove(guild_id).await {
-            send_msg(&ctx, &msg, format!("Failed: {:?}", reason).as_str()).await;
-        }
-
-        send_msg(&ctx, &msg, "Left the voice channel").await;
-    } else {
-        send_msg(&ctx, &msg, "Not in a voice channel").await;
-    }
-
-    Ok(())
-}
-
-
-// plays audio from requested URL in the current voice channel
-#[command]
-#[only_in(guilds)]
-async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let url_or_search = args.rest();
-    let mut should_search = false;
-    if !url_or_search.starts_with("http") {
-        //send_msg(&ctx, &msg, "Must provide a valid URL").await;
-        //return Ok(());
-        should_search = true;
-    }
-
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
-
-    let handler_option = manager.get(guild_id);
-    if let None = handler_option {
-        if let Err(_) = join_impl(ctx, msg).await {}
-    }
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let mut handler = handler_lock.lock().await;
-
-        let source =
-            if should_search {
-                songbird::input::ytdl_search(&url_or_search).await
-            } else {
-                songbird::input::ytdl(&url_or_search).await
-            };
-
-        let source = match source {
-            Ok(source) => source,
-            Err(reason) => {
-                println!("Error starting source: {:?}", reason);
-                send_msg(&ctx, &msg, &format!("Error starting source: {:?}", reason)).await;
-                return Ok(());
-            },
-        };
-
-        {
-            let source_url_option = (&source.metadata.source_url).clone();
-            let source_url = source_url_option.unwrap_or("Unable to extract source URL".to_string());
-            let queue_or_play = if handler.queue().is_empty() { "Playing" } else { "Queuing" };
-            send_msg(&ctx, &msg, &format!("{} audio ({})", queue_or_play, source_url)).await;
-        }
-
-        handler.enqueue_source(source);
-    } else {
-        send_msg(&ctx, &msg, "Not in a voice channel").await;
-    }
-
-    Ok(())
-}
-
-// skips currently playing audio
-#[command]
-#[only_in(guilds)]
-async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-
-        match handler.queue().skip() {
-            Ok(_) => {},
-            Err(reason) => send_msg(&ctx, &msg, &format!("Error skipping audio: {:?}", reason)).await,
-        }
-
-        send_msg(&ctx, &msg, "Skipped audio").await;
-    } else {
-        send_msg(&ctx, &msg, "Not in a voice channel").await;
-    }
-
-    Ok(())
-}
-
-// stops all audio playback
-#[command]
-#[only_in(guilds)]
-async fn stop(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-
-        handler.queue().stop();
-
-        send_msg(&ctx, &msg, "Stopped audio playback").await;
-    } else {
-        send_msg(&ctx, &msg, "Not in a voice channel").await;
-    }
-
-    Ok(())
-}
-
-// sends current audio playback info
-#[command]
-#[only_in(guilds)]
-async fn np(ctx: &Context, msg: &Message) -> CommandResult {
-    let guild = msg.guild(&ctx.cache).await.unwrap();
-    let guild_id = guild.id;
-
-    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
-
-    if let Some(handler_lock) = manager.get(guild_id) {
-        let handler = handler_lock.lock().await;
-
-        let current_track_or_error = handler.queue().current();
-        let current_track = match current_track_or_error {
-            Some(current_track) => current_track,
-            None => {
-                send_msg(&ctx, &msg, "No audio track appears to be playing at the moment").await;
-                return Ok(());
-            }
-        };
-        let song_title = current_track.metadata().title.clone();
-        let song_track = current_track.metadata().track.clone();
-        let song_artist = current_track.metadata().artist.clone();
-        let song_yt_channel = current_track.metadata().channel.clone();
-        let song_url = current_track.metadata().source_url.clone();
-
-        let mut song_string = "Currently playing audio track:\n".to_string();
-        song_string.push_str(&format!("    title: {}\n", song_title.unwrap_or("none".to_string())));
-        song_string.push_str(&format!("    track: {}\n", song_track.unwrap_or("none".to_string())));
-        song_string.push_str(&format!("    artist: {}\n", song_artist.unwrap_or("none".to_string())));
-        song_string.push_str(&format!("    YouTube channel: {}\n", song_yt_channel.unwrap_or("none".to_string())));
-        song_string.push_str(&format!("    URL: <{}>", song_url.unwrap_or("none".to_string())));
-        send_msg(&ctx, &msg, &song_string).await;
-    } else {
-        send_msg(&ctx, &msg, "Not in a voice channel").await;
-    }
-
+    send_msg(ctx, msg, &format!("Activity set to \"Playing {}\"", activity)).await;
     Ok(())
 }
 
@@ -423,7 +319,7 @@ async fn say(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
     let content = content_safe(&ctx.cache, &args.rest(), &settings).await;
 
-    send_msg(&ctx, &msg, &content).await;
+    send_msg(ctx, msg, &content).await;
 
     Ok(())
 }
@@ -431,11 +327,11 @@ async fn say(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 // sends a DM to the specified user
 #[command]
 async fn dm(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let user = &msg.mentions.get(0);
+    let user = &msg.mentions.first();
     match user {
         Some(user) => user,
         None => {
-            send_msg(&ctx, &msg, "Mention someone to DM! uwu").await;
+            send_msg(ctx, msg, "Mention someone to DM! uwu").await;
 
             return Ok(());
         },
@@ -453,7 +349,7 @@ async fn dm(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         m
     }).await;
 
-    send_msg(&ctx, &msg, "Message sent! :3").await;
+    send_msg(ctx, msg, "Message sent! :3").await;
 
     Ok(())
 }
@@ -465,7 +361,7 @@ async fn boop(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let mut boop_receiver = match parsed_args.single::<String>() {
         Ok(boop_receiver) => boop_receiver,
         Err(_) => {
-            send_msg(&ctx, &msg, "Mention someone to boop! uwu").await;
+            send_msg(ctx, msg, "Mention someone to boop! uwu").await;
 
             return Ok(());
         },
@@ -481,63 +377,26 @@ async fn boop(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     output.push_str(&boop_receiver);
     output.push_str("* :3");
 
-    send_msg(&ctx, &msg, &output).await;
+    send_msg(ctx, msg, &output).await;
 
     Ok(())
 }
 
-// sends a user's profile picture
+// sends the URL of a user's profile picture, or of any other image a command can work on
 #[command]
-async fn pfp(ctx: &Context, msg: &Message) -> CommandResult {
-    let user = &msg.mentions.get(0).unwrap_or(&msg.author);
-    let pfp_url = match user.avatar_url() {
-        Some(pfp_url) => pfp_url,
-        None => {
-            send_msg(&ctx, &msg, "Failed to get URL for user").await;
-            return Ok(());
-        },
-    };
-
-    send_msg(&ctx, &msg, &pfp_url).await;
+async fn pfp(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    match images::resolve_image_url(ctx, msg, args.rest()).await {
+        Ok(url) => send_msg(ctx, msg, &url).await,
+        Err(reason) => send_msg(ctx, msg, &format!("Failed to get the image URL: {}", reason)).await,
+    }
 
     Ok(())
 }
 
-// inverts a user's profile picture
+// inverts the colors of a user's profile picture, or of any other image a command can work on
 #[command]
-async fn invert(ctx: &Context, msg: &Message) -> CommandResult {
-    let user = &msg.mentions.get(0).unwrap_or(&msg.author);
-    let pfp_url = match user.avatar_url() {
-        Some(pfp_url) => pfp_url,
-        None => {
-            send_msg(&ctx, &msg, "Failed to get URL for user").await;
-            return Ok(());
-        },
-    };
-
-    let response = reqwest::get(&pfp_url).await?;
-    let content = response.bytes().await?;
-
-    let file = Builder::new().suffix(".png").tempfile()?;
-
-    let (width, height, buf) = WebPDecodeRGB(content.as_ref())?;
-    let mut pixel_buf = match RgbImage::from_vec(width, height, buf.to_vec()) {
-        Some(pixel_buf) => pixel_buf,
-        None => return Ok(())
-    };
-
-    imageops::invert(&mut pixel_buf);
-
-    let file_path = match file.path().to_str() {
-        Some(file_path) => file_path,
-        None => return Ok(())
-    };
-    println!("temp file location: {:?}", file_path);
-    pixel_buf.save(file_path)?;
-    let path = vec![file_path];
-    send_file(&ctx, &msg, path).await;
-
-    Ok(())
+async fn invert(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    effects::apply_effect(ctx, msg, args.rest(), imageops::invert).await
 }
 
 // sends a small image of a specified hexadecimal color code
@@ -548,16 +407,16 @@ async fn color(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let color_code_string = match parsed_args.single::<String>() {
         Ok(color_code_string) => color_code_string,
         Err(_) => {
-            send_msg(&ctx, &msg, "Specify a hexadecimal color code (for example: `B28FEB`)").await;
+            send_msg(ctx, msg, "Specify a hexadecimal color code (for example: `B28FEB`)").await;
 
             return Ok(());
         },
     };
-    let color_code_or_error = hex::decode(color_code_string);
+    let color_code_or_error = hex::decode(&color_code_string);
     let color_code = match color_code_or_error {
         Ok(color_code) => color_code,
         Err(reason) => {
-            send_msg(&ctx, &msg, &format!("An error occurred while parsing the hexadecimal color code: {}", reason)).await;
+            send_msg(ctx, msg, &format!("An error occurred while parsing the hexadecimal color code: {}", reason)).await;
 
             return Ok(());
         }
@@ -565,7 +424,7 @@ async fn color(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
     let mut value4 = 255;
     if color_code.len() != 3 && color_code.len() != 4 {
-        send_msg(&ctx, &msg, "An error occurred while parsing the hexadecimal color code: Must provide 3 or 4 bytes (RGB/RGBA)").await;
+        send_msg(ctx, msg, "An error occurred while parsing the hexadecimal color code: Must provide 3 or 4 bytes (RGB/RGBA)").await;
         return Ok(());
     }
 
@@ -573,26 +432,23 @@ async fn color(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         value4 = color_code[3]
     }
 
-    let file = Builder::new().suffix(".png").tempfile()?;
-
     let pixel_buf = ImageBuffer::from_fn(64, 64, |_, _| {
         image::Rgba([color_code[0], color_code[1], color_code[2], value4])
     });
 
-    let file_path = match file.path().to_str() {
-        Some(file_path) => file_path,
-        None => return Ok(())
-    };
-    println!("temp file location: {:?}", file_path);
-    pixel_buf.save(file_path)?;
-    let path = vec![file_path];
-    send_file(&ctx, &msg, path).await;
+    match images::encode_image(vec![Frame::new(pixel_buf)]) {
+        Ok(image) => send_image(ctx, msg, &color_code_string.to_lowercase(), &image).await,
+        Err(reason) => {
+            println!("Error encoding image: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error encoding image: {}", reason)).await;
+        },
+    }
 
     Ok(())
 }
 
 #[command]
 async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
-    send_msg(&ctx, &msg, "Pong!").await;
+    send_msg(ctx, msg, "Pong!").await;
     Ok(())
 }
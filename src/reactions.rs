@@ -0,0 +1,491 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group},
+    },
+    model::{
+        channel::{Message, ReactionType},
+        guild::Guild,
+        misc::Mentionable,
+    },
+};
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+use crate::{react_msg, send_msg, settings, shorten};
+
+#[group]
+#[commands(reactions, react)]
+pub struct Reactions;
+
+const MAX_RULES_PER_GUILD: usize = 50;
+const MAX_KEYWORD_LENGTH: usize = 200;
+// keywords are shortened in the list so a page stays below Discord's limit of 2000 characters per message
+const RULES_PER_PAGE: usize = 10;
+const MAX_LISTED_KEYWORD_LENGTH: usize = 100;
+
+// a keyword reaction rule, matched case-insensitively against the message content
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReactionRule {
+    pub keyword: String,
+    pub emoji: ReactionType,
+    #[serde(default)]
+    pub mode: MatchMode,
+    // compiled on the first match, `None` if the pattern is invalid
+    #[serde(skip)]
+    regex: OnceLock<Option<Regex>>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    // the keyword must not be surrounded by word characters, so "cat" doesn't match "concatenate"
+    #[default]
+    Word,
+    // the keyword may appear anywhere, including inside other words
+    Substring,
+    // the keyword is a regular expression
+    Regex,
+}
+
+impl FromStr for MatchMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "word" => Ok(MatchMode::Word),
+            "substring" => Ok(MatchMode::Substring),
+            "regex" => Ok(MatchMode::Regex),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for MatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchMode::Word => f.write_str("word"),
+            MatchMode::Substring => f.write_str("substring"),
+            MatchMode::Regex => f.write_str("regex"),
+        }
+    }
+}
+
+impl ReactionRule {
+    fn new(keyword: &str, emoji: &str, mode: MatchMode) -> ReactionRule {
+        ReactionRule {
+            keyword: keyword.to_string(),
+            emoji: ReactionType::Unicode(emoji.to_string()),
+            mode,
+            regex: OnceLock::new(),
+        }
+    }
+
+    fn build_regex(&self) -> std::result::Result<Regex, regex::Error> {
+        let pattern = match self.mode {
+            // `\b` would never match next to keywords starting or ending with a symbol, such as "c++" or ":3"
+            MatchMode::Word => format!(r"(?:^|\W){}(?:\W|$)", regex::escape(&self.keyword)),
+            MatchMode::Substring => regex::escape(&self.keyword),
+            MatchMode::Regex => self.keyword.clone(),
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .build()
+    }
+
+    fn matches(&self, content: &str) -> bool {
+        match self.regex.get_or_init(|| self.build_regex().ok()) {
+            Some(regex) => regex.is_match(content),
+            None => false,
+        }
+    }
+}
+
+// keeps user-supplied patterns from using excessive amounts of memory
+const REGEX_SIZE_LIMIT: usize = 1 << 18;
+
+// the rules used by guilds that haven't configured their own, shared so their patterns are only compiled once
+pub fn default_rules() -> &'static [ReactionRule] {
+    static DEFAULT_RULES: OnceLock<Vec<ReactionRule>> = OnceLock::new();
+    DEFAULT_RULES.get_or_init(|| vec![
+        ReactionRule::new(r"\b(fox(es)?|focks)\b", "🦊", MatchMode::Regex),
+        ReactionRule::new(r"\bcats?\b", "🐱", MatchMode::Regex),
+        ReactionRule::new(r"\blemons?\b", "🍋", MatchMode::Regex),
+    ])
+}
+
+// reacts to messages matching the guild's reaction rules, unless reactions are disabled in the channel
+pub async fn react_to_keywords(ctx: &Context, msg: &Message) {
+    let emojis = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        if !settings.reactions_enabled(msg.guild_id, msg.channel_id, msg.author.id) {
+            return;
+        }
+
+        let rules = match msg.guild_id.and_then(|guild_id| settings.guild(guild_id)) {
+            Some(guild_settings) => guild_settings.reaction_rules(),
+            None => default_rules(),
+        };
+
+        let mut emojis: Vec<ReactionType> = Vec::new();
+        for rule in rules {
+            if !emojis.contains(&rule.emoji) && rule.matches(&msg.content) {
+                emojis.push(rule.emoji.clone());
+            }
+        }
+        emojis
+    };
+
+    for emoji in emojis {
+        react_msg(ctx, msg, emoji).await;
+    }
+}
+
+// disables reactions in channels named "vent" the first time a guild is seen, like before reactions were configurable,
+// returning whether the settings changed and need to be saved
+pub async fn seed_vent_channels(ctx: &Context, guild: &Guild) -> bool {
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    if settings.guild(guild.id).is_some_and(|guild_settings| guild_settings.vent_channels_seeded) {
+        return false;
+    }
+
+    let guild_settings = settings.guild_mut(guild.id);
+    for (channel_id, channel) in &guild.channels {
+        if channel.name == "vent" {
+            guild_settings.reaction_channels.entry(*channel_id).or_insert(false);
+        }
+    }
+    guild_settings.vent_channels_seeded = true;
+
+    true
+}
+
+// lists where keyword reactions are enabled in the current guild
+#[command]
+#[only_in(guilds)]
+#[sub_commands(reactions_on, reactions_off, reactions_me, reactions_list)]
+async fn reactions(ctx: &Context, msg: &Message) -> CommandResult {
+    list_impl(ctx, msg).await
+}
+
+#[command("list")]
+#[only_in(guilds)]
+async fn reactions_list(ctx: &Context, msg: &Message) -> CommandResult {
+    list_impl(ctx, msg).await
+}
+
+// enables keyword reactions in the current channel, or in the whole guild if `guild` is passed
+#[command("on")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_CHANNELS)]
+async fn reactions_on(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    set_impl(ctx, msg, args, true).await
+}
+
+// disables keyword reactions in the current channel, or in the whole guild if `guild` is passed
+#[command("off")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_CHANNELS)]
+async fn reactions_off(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    set_impl(ctx, msg, args, false).await
+}
+
+// enables or disables keyword reactions on the requesting user's own messages, in every server
+#[command("me")]
+async fn reactions_me(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let enabled = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            send_msg(ctx, msg, "Usage: `reactions me on|off`").await;
+            return Ok(());
+        },
+    };
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.user_mut(msg.author.id).reactions_enabled = enabled;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Keyword reactions {} for your messages", enabled_string(enabled))).await;
+
+    Ok(())
+}
+
+async fn list_impl(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let settings = settings_lock.read().await;
+
+    let (guild_enabled, mut channels) = match settings.guild(guild_id) {
+        Some(guild_settings) => (guild_settings.reactions_enabled, guild_settings.reaction_channels.iter().collect::<Vec<_>>()),
+        None => (true, Vec::new()),
+    };
+    channels.sort();
+
+    let mut list_string = format!("Keyword reactions are {} in this server\n", enabled_string(guild_enabled));
+    if !channels.is_empty() {
+        list_string.push_str("Channel overrides:\n");
+        for (channel_id, enabled) in channels {
+            list_string.push_str(&format!("    {}: {}\n", channel_id.mention(), enabled_string(*enabled)));
+        }
+    }
+    send_msg(ctx, msg, &list_string).await;
+
+    Ok(())
+}
+
+async fn set_impl(ctx: &Context, msg: &Message, mut args: Args, enabled: bool) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let whole_guild = match args.single::<String>() {
+        Ok(scope) if scope == "guild" || scope == "server" => true,
+        Ok(_) => {
+            send_msg(ctx, msg, "Usage: `reactions on|off [guild]`").await;
+            return Ok(());
+        },
+        Err(_) => false,
+    };
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    let guild_settings = settings.guild_mut(guild_id);
+    if whole_guild {
+        guild_settings.reactions_enabled = enabled;
+    } else if enabled == guild_settings.reactions_enabled {
+        // the channel now matches the guild, so it no longer needs an override
+        guild_settings.reaction_channels.remove(&msg.channel_id);
+    } else {
+        guild_settings.reaction_channels.insert(msg.channel_id, enabled);
+    }
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    let target = if whole_guild { "this server".to_string() } else { msg.channel_id.mention().to_string() };
+    send_msg(ctx, msg, &format!("Keyword reactions {} in {}", enabled_string(enabled), target)).await;
+
+    Ok(())
+}
+
+fn enabled_string(enabled: bool) -> &'static str {
+    if enabled { "enabled" } else { "disabled" }
+}
+
+// lists the keyword reaction rules of the current guild, 10 per page
+#[command]
+#[only_in(guilds)]
+#[sub_commands(react_add, react_remove, react_list)]
+async fn react(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    react_list_impl(ctx, msg, args).await
+}
+
+#[command("list")]
+#[only_in(guilds)]
+async fn react_list(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    react_list_impl(ctx, msg, args).await
+}
+
+// adds a rule reacting with an emoji to messages containing a keyword
+#[command("add")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn react_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let usage = "Usage: `react add <keyword> <emoji> [word|substring|regex]` (use quotes for keywords containing spaces)";
+    let keyword = match args.single_quoted::<String>() {
+        Ok(keyword) if !keyword.is_empty() => keyword,
+        _ => {
+            send_msg(ctx, msg, usage).await;
+            return Ok(());
+        },
+    };
+    if keyword.chars().count() > MAX_KEYWORD_LENGTH {
+        send_msg(ctx, msg, &format!("Keywords can be at most {} characters long", MAX_KEYWORD_LENGTH)).await;
+        return Ok(());
+    }
+    let emoji = match args.single::<ReactionType>() {
+        Ok(emoji) => emoji,
+        Err(_) => {
+            send_msg(ctx, msg, usage).await;
+            return Ok(());
+        },
+    };
+    let mode = match args.single::<String>() {
+        Ok(mode) => match mode.parse::<MatchMode>() {
+            Ok(mode) => mode,
+            Err(_) => {
+                send_msg(ctx, msg, usage).await;
+                return Ok(());
+            },
+        },
+        Err(_) => MatchMode::default(),
+    };
+
+    let rule = ReactionRule { keyword, emoji, mode, regex: OnceLock::new() };
+    if let Err(reason) = rule.build_regex() {
+        send_msg(ctx, msg, &format!("Invalid regular expression: {}", reason)).await;
+        return Ok(());
+    }
+
+    // reacting to the command itself makes sure the emoji is one the bot can actually use
+    if msg.react(&ctx.http, rule.emoji.clone()).await.is_err() {
+        send_msg(ctx, msg, "Unable to react with that emoji (custom emoji must be from a server the bot is in)").await;
+        return Ok(());
+    }
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    let rules = settings.guild_mut(guild_id).reaction_rules_mut();
+    if rules.len() >= MAX_RULES_PER_GUILD {
+        send_msg(ctx, msg, &format!("This server already has the maximum of {} reaction rules", MAX_RULES_PER_GUILD)).await;
+        return Ok(());
+    }
+    let rule_string = format!("`{}` → {} ({})", shorten(&rule.keyword, MAX_LISTED_KEYWORD_LENGTH), rule.emoji, rule.mode);
+    rules.push(rule);
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Added reaction rule {}", rule_string)).await;
+
+    Ok(())
+}
+
+// removes all rules with the specified keyword
+#[command("remove")]
+#[aliases(delete)]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn react_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let keyword = match args.single_quoted::<String>() {
+        Ok(keyword) => keyword,
+        Err(_) => {
+            send_msg(ctx, msg, "Usage: `react remove <keyword>`").await;
+            return Ok(());
+        },
+    };
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    let rules = settings.guild_mut(guild_id).reaction_rules_mut();
+    let rule_count = rules.len();
+    rules.retain(|rule| rule.keyword != keyword);
+    if rules.len() == rule_count {
+        send_msg(ctx, msg, &format!("No reaction rule with the keyword `{}`", keyword)).await;
+        return Ok(());
+    }
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Removed reaction rule `{}`", keyword)).await;
+
+    Ok(())
+}
+
+async fn react_list_impl(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let settings = settings_lock.read().await;
+
+    let rules = match settings.guild(guild_id) {
+        Some(guild_settings) => guild_settings.reaction_rules(),
+        None => default_rules(),
+    };
+
+    if rules.is_empty() {
+        send_msg(ctx, msg, "This server has no reaction rules").await;
+        return Ok(());
+    }
+
+    let page_count = rules.len().div_ceil(RULES_PER_PAGE);
+    let page = args.single::<usize>().unwrap_or(1).clamp(1, page_count);
+
+    let mut list_string = format!("Reaction rules (page {}/{}):\n", page, page_count);
+    for rule in rules.iter().skip((page - 1) * RULES_PER_PAGE).take(RULES_PER_PAGE) {
+        list_string.push_str(&format!("    `{}` → {} ({})\n", shorten(&rule.keyword, MAX_LISTED_KEYWORD_LENGTH), rule.emoji, rule.mode));
+    }
+    send_msg(ctx, msg, &list_string).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(keyword: &str, mode: MatchMode, content: &str) -> bool {
+        ReactionRule::new(keyword, "🦊", mode).matches(content)
+    }
+
+    #[test]
+    fn word_mode_matches_whole_words_only() {
+        assert!(matches("cat", MatchMode::Word, "my cat is asleep"));
+        assert!(matches("cat", MatchMode::Word, "Cat!"));
+        assert!(matches("cat", MatchMode::Word, "cat"));
+        assert!(!matches("cat", MatchMode::Word, "concatenate"));
+        assert!(!matches("cat", MatchMode::Word, "cats"));
+    }
+
+    #[test]
+    fn word_mode_matches_keywords_with_symbols() {
+        assert!(matches("c++", MatchMode::Word, "I write c++ at work"));
+        assert!(matches("c++", MatchMode::Word, "c++"));
+        assert!(!matches("c++", MatchMode::Word, "abc++"));
+        assert!(matches(":3", MatchMode::Word, "hello :3"));
+        assert!(!matches(":3", MatchMode::Word, "ratio 1:3"));
+    }
+
+    #[test]
+    fn substring_mode_matches_inside_words() {
+        assert!(matches("cat", MatchMode::Substring, "concatenate"));
+        assert!(matches("a.b", MatchMode::Substring, "xa.by"));
+        assert!(!matches("a.b", MatchMode::Substring, "axb"));
+    }
+
+    #[test]
+    fn regex_mode_uses_the_pattern() {
+        assert!(matches(r"\bfox(es)?\b", MatchMode::Regex, "two FOXES"));
+        assert!(!matches(r"\bfox(es)?\b", MatchMode::Regex, "firefox"));
+        assert!(!matches("(", MatchMode::Regex, "("));
+    }
+
+    #[test]
+    fn default_rules_are_valid() {
+        for rule in default_rules() {
+            assert!(rule.build_regex().is_ok(), "invalid default rule {}", rule.keyword);
+        }
+        assert!(!default_rules().iter().any(|rule| rule.matches("concatenate")));
+    }
+}
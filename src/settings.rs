@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::prelude::{ChannelId, GuildId, RoleId, UserId},
+    prelude::*,
+};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    reactions::{self, ReactionRule},
+    storage::{self, Storage},
+    Result,
+};
+
+// stored in the data directory
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+// in percent
+pub const DEFAULT_VOLUME: u32 = 100;
+
+pub struct SettingsContainer;
+impl TypeMapKey for SettingsContainer {
+    type Value = Arc<RwLock<Settings>>;
+}
+
+// settings that can be changed at runtime, written back to storage after every change
+pub struct Settings {
+    storage: Box<dyn Storage>,
+    document: SettingsDocument,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SettingsDocument {
+    version: u64,
+    #[serde(default)]
+    guilds: HashMap<GuildId, GuildSettings>,
+    #[serde(default)]
+    users: HashMap<UserId, UserSettings>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GuildSettings {
+    // overrides the default command prefix
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default = "default_true")]
+    pub reactions_enabled: bool,
+    // per-channel overrides of `reactions_enabled`
+    #[serde(default)]
+    pub reaction_channels: HashMap<ChannelId, bool>,
+    // `None` until the guild changes its rules, so guilds keep following the built-in defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reaction_rules: Option<Vec<ReactionRule>>,
+    // whether channels named "vent", which used to be excluded from reactions, have been given an override
+    #[serde(default)]
+    pub vent_channels_seeded: bool,
+    // playback volume in percent, applied to every track queued in the guild
+    #[serde(default = "default_volume")]
+    pub volume: u32,
+    // minutes without audio before leaving the voice channel, 0 for never, `None` for the configured default
+    #[serde(default)]
+    pub idle_timeout: Option<u64>,
+    // whether to leave the voice channel as soon as everyone else has left it
+    #[serde(default = "default_true")]
+    pub leave_when_alone: bool,
+    // whether to warn a minute before leaving due to inactivity
+    #[serde(default)]
+    pub idle_warning: bool,
+    // whether the saved voice session is resumed without asking after a restart
+    #[serde(default)]
+    pub auto_resume: bool,
+    // the role needed to control playback, `None` to let everyone
+    #[serde(default)]
+    pub dj_role: Option<RoleId>,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        GuildSettings {
+            prefix: None,
+            reactions_enabled: true,
+            reaction_channels: HashMap::new(),
+            reaction_rules: None,
+            vent_channels_seeded: false,
+            volume: DEFAULT_VOLUME,
+            idle_timeout: None,
+            leave_when_alone: true,
+            idle_warning: false,
+            auto_resume: false,
+            dj_role: None,
+        }
+    }
+}
+
+impl GuildSettings {
+    pub fn reaction_rules(&self) -> &[ReactionRule] {
+        self.reaction_rules.as_deref().unwrap_or_else(|| reactions::default_rules())
+    }
+
+    // starts from a copy of the defaults the first time a guild changes its rules
+    pub fn reaction_rules_mut(&mut self) -> &mut Vec<ReactionRule> {
+        self.reaction_rules.get_or_insert_with(|| reactions::default_rules().to_vec())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserSettings {
+    // whether the bot reacts to this user's messages at all
+    #[serde(default = "default_true")]
+    pub reactions_enabled: bool,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        UserSettings {
+            reactions_enabled: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_volume() -> u32 {
+    DEFAULT_VOLUME
+}
+
+impl Settings {
+    // loads settings from the specified storage, upgrading them to the current layout if needed
+    pub fn load(storage: Box<dyn Storage>) -> Result<Settings> {
+        let document = match storage.load()? {
+            Some(document) => serde_json::from_value(storage::migrate(document)?)?,
+            None => SettingsDocument::default(),
+        };
+
+        Ok(Settings { storage, document })
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        self.document.version = storage::CURRENT_VERSION;
+        self.storage.save(&serde_json::to_value(&self.document)?)
+    }
+
+    pub fn guild(&self, guild_id: GuildId) -> Option<&GuildSettings> {
+        self.document.guilds.get(&guild_id)
+    }
+
+    pub fn guild_mut(&mut self, guild_id: GuildId) -> &mut GuildSettings {
+        self.document.guilds.entry(guild_id).or_default()
+    }
+
+    pub fn user(&self, user_id: UserId) -> Option<&UserSettings> {
+        self.document.users.get(&user_id)
+    }
+
+    pub fn user_mut(&mut self, user_id: UserId) -> &mut UserSettings {
+        self.document.users.entry(user_id).or_default()
+    }
+
+    // keyword reactions are always enabled in DMs unless the user opted out
+    pub fn reactions_enabled(&self, guild_id: Option<GuildId>, channel_id: ChannelId, user_id: UserId) -> bool {
+        if let Some(user_settings) = self.user(user_id) {
+            if !user_settings.reactions_enabled {
+                return false;
+            }
+        }
+
+        match guild_id.and_then(|guild_id| self.guild(guild_id)) {
+            Some(guild_settings) => *guild_settings.reaction_channels.get(&channel_id).unwrap_or(&guild_settings.reactions_enabled),
+            None => true,
+        }
+    }
+}
+
+// retrieves the settings handle stored in the client data
+pub async fn get(ctx: &Context) -> Arc<RwLock<Settings>> {
+    let data = ctx.data.read().await;
+    data.get::<SettingsContainer>().expect("Error getting settings").clone()
+}
+
+// saves settings changed outside of a command, where errors can only be logged
+pub async fn save(ctx: &Context) {
+    let settings_lock = get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_reaction_rules_are_only_stored_once_changed() {
+        let mut guild_settings = GuildSettings::default();
+        assert_eq!(guild_settings.reaction_rules().len(), reactions::default_rules().len());
+        assert!(serde_json::to_value(&guild_settings).unwrap().get("reaction_rules").is_none());
+
+        guild_settings.reaction_rules_mut().pop();
+        let stored = serde_json::to_value(&guild_settings).unwrap();
+        assert_eq!(stored["reaction_rules"].as_array().unwrap().len(), reactions::default_rules().len() - 1);
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json::Value;
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use crate::Result;
+
+// the layout version written by this build; bump it and add a step to `migrate` whenever the layout changes
+pub const CURRENT_VERSION: u64 = 2;
+
+// a place to keep a JSON document, such as the settings, between restarts
+pub trait Storage: Send + Sync {
+    // returns `None` if nothing has been stored yet
+    fn load(&self) -> Result<Option<Value>>;
+    fn save(&self, document: &Value) -> Result<()>;
+}
+
+pub struct JsonFileStorage {
+    path: PathBuf,
+}
+
+impl JsonFileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> JsonFileStorage {
+        JsonFileStorage { path: path.into() }
+    }
+}
+
+impl Storage for JsonFileStorage {
+    fn load(&self) -> Result<Option<Value>> {
+        match fs::read(&self.path) {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(reason) if reason.kind() == ErrorKind::NotFound => Ok(None),
+            Err(reason) => Err(reason.into()),
+        }
+    }
+
+    // writes to a temporary file first so a failed write can't corrupt the existing file
+    fn save(&self, document: &Value) -> Result<()> {
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(document)?)?;
+        fs::rename(&temp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+// upgrades a stored document to `CURRENT_VERSION`, one version at a time
+pub fn migrate(mut document: Value) -> Result<Value> {
+    // documents written before versioning was introduced have no version field
+    let mut version = document.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > CURRENT_VERSION {
+        return Err(format!("Settings were written by a newer version of rybot2 (version {}, expected at most {})", version, CURRENT_VERSION).into());
+    }
+
+    while version < CURRENT_VERSION {
+        match version {
+            1 => migrate_v1_to_v2(&mut document)?,
+            _ => unreachable!(),
+        }
+        version += 1;
+        println!("Migrated settings to version {}", version);
+    }
+    document["version"] = Value::from(version);
+
+    Ok(document)
+}
+
+// version 2 adds per-user settings
+fn migrate_v1_to_v2(document: &mut Value) -> Result<()> {
+    let object = match document.as_object_mut() {
+        Some(object) => object,
+        None => return Err("Settings document is not a JSON object".into()),
+    };
+    object.entry("users").or_insert_with(|| Value::Object(Default::default()));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn unversioned_documents_are_migrated_from_version_1() {
+        let document = migrate(json!({ "guilds": { "1": { "prefix": "!" } } })).unwrap();
+        assert_eq!(document, json!({ "version": CURRENT_VERSION, "guilds": { "1": { "prefix": "!" } }, "users": {} }));
+    }
+
+    #[test]
+    fn migration_keeps_existing_users() {
+        let document = migrate(json!({ "version": 1, "users": { "2": { "reactions_enabled": false } } })).unwrap();
+        assert_eq!(document["users"], json!({ "2": { "reactions_enabled": false } }));
+    }
+
+    #[test]
+    fn current_documents_are_unchanged() {
+        let document = json!({ "version": CURRENT_VERSION, "guilds": {}, "users": {} });
+        assert_eq!(migrate(document.clone()).unwrap(), document);
+    }
+
+    #[test]
+    fn newer_documents_are_rejected() {
+        assert!(migrate(json!({ "version": CURRENT_VERSION + 1 })).is_err());
+    }
+
+    #[test]
+    fn non_object_documents_are_rejected() {
+        assert!(migrate(json!([])).is_err());
+    }
+}
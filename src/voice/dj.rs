@@ -0,0 +1,161 @@
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandOptions, CommandResult, Reason,
+        macros::{check, command},
+    },
+    model::{
+        channel::Message,
+        misc::Mentionable,
+        prelude::RoleId,
+    },
+    utils::parse_role,
+};
+
+use super::track_request;
+use crate::{send_msg, settings};
+
+// whether the author may control playback: always if no DJ role is set, otherwise only server managers and members with the role
+pub async fn is_dj(ctx: &Context, msg: &Message) -> bool {
+    let guild = match msg.guild(&ctx.cache).await {
+        Some(guild) => guild,
+        None => return false,
+    };
+
+    let dj_role = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        settings.guild(guild.id).and_then(|guild_settings| guild_settings.dj_role)
+    };
+    let dj_role = match dj_role {
+        Some(dj_role) => dj_role,
+        None => return true,
+    };
+
+    if guild.member_permissions(ctx, msg.author.id).await.is_ok_and(|permissions| permissions.manage_guild()) {
+        return true;
+    }
+
+    match guild.member(ctx, msg.author.id).await {
+        Ok(member) => member.roles.contains(&dj_role),
+        Err(_) => false,
+    }
+}
+
+pub async fn dj_role_string(ctx: &Context, msg: &Message) -> String {
+    let settings_lock = settings::get(ctx).await;
+    let settings = settings_lock.read().await;
+    msg.guild_id.and_then(|guild_id| settings.guild(guild_id)).and_then(|guild_settings| guild_settings.dj_role)
+        .map_or_else(|| "the DJ role".to_string(), |dj_role| dj_role.mention().to_string())
+}
+
+// applied to commands that affect everyone listening, such as `stop` and `leave`
+#[check]
+#[name = "DJ"]
+async fn dj_check(ctx: &Context, msg: &Message, _args: &mut Args, _options: &CommandOptions) -> Result<(), Reason> {
+    if is_dj(ctx, msg).await {
+        Ok(())
+    } else {
+        Err(Reason::User(format!("Only members with {} can use this command", dj_role_string(ctx, msg).await)))
+    }
+}
+
+// like the DJ check, but also lets the requester of the current track through
+#[check]
+#[name = "Requester"]
+async fn requester_check(ctx: &Context, msg: &Message, _args: &mut Args, _options: &CommandOptions) -> Result<(), Reason> {
+    if is_dj(ctx, msg).await {
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+    let current_track = match msg.guild_id.and_then(|guild_id| manager.get(guild_id)) {
+        Some(handler_lock) => handler_lock.lock().await.queue().current(),
+        None => None,
+    };
+    if let Some(current_track) = current_track {
+        if track_request(&current_track).await.is_some_and(|request| request.user_id == msg.author.id) {
+            return Ok(());
+        }
+    }
+
+    Err(Reason::User(format!("Only members with {} can skip other people's tracks", dj_role_string(ctx, msg).await)))
+}
+
+// shows the DJ role of the current guild
+#[command]
+#[only_in(guilds)]
+#[sub_commands(dj_set, dj_reset)]
+async fn dj(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let dj_role = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        settings.guild(guild_id).and_then(|guild_settings| guild_settings.dj_role)
+    };
+
+    match dj_role {
+        Some(dj_role) => send_msg(ctx, msg, &format!(
+            "Only members with {} can control playback, everyone can still queue tracks and skip their own",
+            dj_role.mention(),
+        )).await,
+        None => send_msg(ctx, msg, "No DJ role is set, so everyone can control playback").await,
+    }
+
+    Ok(())
+}
+
+// sets the role needed to control playback, by mention, ID or name
+#[command("set")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn dj_set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+
+    let role_string = args.rest().trim();
+    let dj_role = parse_role(role_string)
+        .or_else(|| role_string.parse::<u64>().ok())
+        .map(RoleId)
+        .filter(|role_id| guild.roles.contains_key(role_id))
+        .or_else(|| guild.role_by_name(role_string).map(|role| role.id));
+    let dj_role = match dj_role {
+        Some(dj_role) => dj_role,
+        None => {
+            send_msg(ctx, msg, "Usage: `dj set <role>` (a mention, ID or name of a role in this server)").await;
+            return Ok(());
+        },
+    };
+
+    set_impl(ctx, msg, Some(dj_role)).await
+}
+
+// removes the DJ role, letting everyone control playback again
+#[command("reset")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn dj_reset(ctx: &Context, msg: &Message) -> CommandResult {
+    set_impl(ctx, msg, None).await
+}
+
+async fn set_impl(ctx: &Context, msg: &Message, dj_role: Option<RoleId>) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.guild_mut(guild_id).dj_role = dj_role;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    match dj_role {
+        Some(dj_role) => send_msg(ctx, msg, &format!("DJ role set to {}", dj_role.mention())).await,
+        None => send_msg(ctx, msg, "DJ role removed, everyone can control playback").await,
+    }
+
+    Ok(())
+}
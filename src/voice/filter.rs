@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::channel::Message,
+};
+use songbird::{
+    input::{
+        error::Result as InputResult,
+        restartable::Restart,
+        Codec, Container, Input, Metadata, Restartable,
+    },
+    tracks::{LoopState, TrackHandle},
+    typemap::TypeMapKey,
+};
+use std::{fmt, time::Duration};
+
+use super::{dj_role_string, enqueue, is_dj, player_states, track::ffmpeg_from, track_request, LoopMode, TrackSource};
+use crate::send_msg;
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    #[default]
+    Off,
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    Normalize,
+}
+
+impl Filter {
+    fn parse(name: &str) -> Option<Filter> {
+        match name {
+            "off" | "none" => Some(Filter::Off),
+            "bassboost" | "bass" => Some(Filter::BassBoost),
+            "nightcore" => Some(Filter::Nightcore),
+            "vaporwave" => Some(Filter::Vaporwave),
+            "normalize" | "normalise" => Some(Filter::Normalize),
+            _ => None,
+        }
+    }
+
+    // the ffmpeg filter chain, resampled first since the pitch shifts depend on the sample rate
+    fn audio_filter(self) -> Option<&'static str> {
+        match self {
+            Filter::Off => None,
+            Filter::BassBoost => Some("bass=g=12:f=110:w=0.6"),
+            Filter::Nightcore => Some("aresample=48000,asetrate=60000,aresample=48000"),
+            Filter::Vaporwave => Some("aresample=48000,asetrate=38400,aresample=48000"),
+            Filter::Normalize => Some("dynaudnorm"),
+        }
+    }
+
+    // how much faster than the original the filtered audio plays
+    pub fn speed(self) -> f64 {
+        match self {
+            Filter::Nightcore => 1.25,
+            Filter::Vaporwave => 0.8,
+            _ => 1.0,
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Off => f.write_str("off"),
+            Filter::BassBoost => f.write_str("bassboost"),
+            Filter::Nightcore => f.write_str("nightcore"),
+            Filter::Vaporwave => f.write_str("vaporwave"),
+            Filter::Normalize => f.write_str("normalize"),
+        }
+    }
+}
+
+// the filter a track was recreated with, tracks without one are unfiltered
+struct FilterContainer;
+impl TypeMapKey for FilterContainer {
+    type Value = Filter;
+}
+
+pub async fn track_filter(track: &TrackHandle) -> Filter {
+    track.typemap().read().await.get::<FilterContainer>().copied().unwrap_or_default()
+}
+
+pub async fn set_track_filter(track: &TrackHandle, filter: Filter) {
+    track.typemap().write().await.insert::<FilterContainer>(filter);
+}
+
+// starts a source through a filter, `metadata` has to be in the filtered audio's time
+pub async fn filtered_input(source: TrackSource, filter: Filter, metadata: Metadata) -> InputResult<Input> {
+    Ok(Restartable::new(FilterRestarter { source, filter, metadata }, true).await?.into())
+}
+
+// starts a track's source again through the filter it was playing with, for the queue loop
+pub async fn recreate_input(track: &TrackHandle, source: &TrackSource) -> InputResult<(Input, Filter)> {
+    let filter = track_filter(track).await;
+    let input = match filter {
+        Filter::Off => source.create_input().await?,
+        filter => filtered_input(source.clone(), filter, track.metadata().clone()).await?,
+    };
+
+    Ok((input, filter))
+}
+
+// restarts a source through ffmpeg with a filter, positions are in the filtered audio's time
+struct FilterRestarter {
+    source: TrackSource,
+    filter: Filter,
+    metadata: Metadata,
+}
+
+#[async_trait]
+impl Restart for FilterRestarter {
+    async fn call_restart(&mut self, time: Option<Duration>) -> InputResult<Input> {
+        let time = time.unwrap_or_default().mul_f64(self.filter.speed());
+        ffmpeg_from(&self.source, time, self.filter.audio_filter())
+    }
+
+    async fn lazy_init(&mut self) -> InputResult<(Option<Metadata>, Codec, Container)> {
+        Ok((Some(self.metadata.clone()), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+// plays the current track through an audio filter, or without one if the filter is `off`
+#[command]
+#[aliases(filters, effect)]
+#[only_in(guilds)]
+async fn filter(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let filter = match args.single::<String>() {
+        Ok(name) => match Filter::parse(&name.to_lowercase()) {
+            Some(filter) => Some(filter),
+            None => {
+                send_msg(ctx, msg, "Usage: `filter bassboost|nightcore|vaporwave|normalize|off`").await;
+                return Ok(());
+            },
+        },
+        Err(_) => None,
+    };
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let mut handler = handler_lock.lock().await;
+
+        let current_track = match handler.queue().current() {
+            Some(current_track) => current_track,
+            None => {
+                send_msg(ctx, msg, "No audio track appears to be playing at the moment").await;
+                return Ok(());
+            }
+        };
+        let current_filter = track_filter(&current_track).await;
+
+        let filter = match filter {
+            Some(filter) => filter,
+            None => {
+                send_msg(ctx, msg, &format!("The current track's filter is {} (use `filter bassboost|nightcore|vaporwave|normalize|off` to change it)", current_filter)).await;
+                return Ok(());
+            },
+        };
+        // anyone can see the filter, but changing it is up to DJs
+        if !is_dj(ctx, msg).await {
+            send_msg(ctx, msg, &format!("Only members with {} can change the filter", dj_role_string(ctx, msg).await)).await;
+            return Ok(());
+        }
+        if filter == current_filter {
+            send_msg(ctx, msg, &format!("The current track's filter is already {}", filter)).await;
+            return Ok(());
+        }
+
+        // only queued tracks remember where they came from
+        let request = match track_request(&current_track).await {
+            Some(request) => request,
+            None => {
+                send_msg(ctx, msg, "The current audio track can't be filtered").await;
+                return Ok(());
+            },
+        };
+        let track_state = match current_track.get_info().await {
+            Ok(track_state) => track_state,
+            Err(reason) => {
+                send_msg(ctx, msg, &format!("Error filtering audio: {:?}", reason)).await;
+                return Ok(());
+            },
+        };
+
+        // positions and durations are converted through the original audio's time, since some filters change the speed
+        let speed_ratio = current_filter.speed() / filter.speed();
+        let position = track_state.position.mul_f64(speed_ratio);
+        let mut metadata = current_track.metadata().clone();
+        metadata.duration = metadata.duration.map(|duration| duration.mul_f64(speed_ratio));
+        metadata.channels = Some(2);
+        metadata.sample_rate = Some(48000);
+
+        let input = match filtered_input(request.source.clone(), filter, metadata).await {
+            Ok(input) => input,
+            Err(reason) => {
+                println!("Error starting source: {:?}", reason);
+                send_msg(ctx, msg, &format!("Error starting source: {:?}", reason)).await;
+                return Ok(());
+            },
+        };
+
+        let track_handle = enqueue(&mut handler, input, request, track_state.volume).await;
+        set_track_filter(&track_handle, filter).await;
+        let _ = track_handle.seek_time(position);
+
+        // the filtered track takes over from the current one, along with its loop
+        let loop_mode = player_states(ctx).await.read().await.get(&guild_id).map(|player_state| player_state.loop_mode).unwrap_or_default();
+        if loop_mode == LoopMode::Track && track_state.loops == LoopState::Infinite {
+            let _ = current_track.disable_loop();
+            let _ = track_handle.enable_loop();
+        }
+        handler.queue().modify_queue(|queue| {
+            if let Some(track) = queue.pop_back() {
+                queue.insert(1, track);
+            }
+        });
+        let _ = handler.queue().skip();
+
+        send_msg(ctx, msg, &format!("Filter set to {}", filter)).await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,223 @@
+use serenity::{
+    cache::Cache,
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::{
+        channel::Message,
+        prelude::{ChannelId, GuildId, UserId},
+    },
+};
+
+use super::{leave_voice, player_states, sessions};
+use crate::{config, send_channel_msg, send_msg, settings};
+
+// the users other than bots in a voice channel, or `None` if the guild isn't cached
+pub async fn listeners(cache: &Cache, guild_id: GuildId, channel_id: ChannelId) -> Option<Vec<UserId>> {
+    let voice_states = cache.guild_field(guild_id, |guild| guild.voice_states.clone()).await?;
+
+    let mut listeners = Vec::new();
+    for (user_id, voice_state) in voice_states {
+        if voice_state.channel_id != Some(channel_id) {
+            continue;
+        }
+        let is_bot = match &voice_state.member {
+            Some(member) => member.user.bot,
+            None => cache.user(user_id).await.is_some_and(|user| user.bot),
+        };
+        if !is_bot {
+            listeners.push(user_id);
+        }
+    }
+
+    Some(listeners)
+}
+
+// called on every voice state update, since that's when the bot can end up alone
+pub async fn leave_if_alone(ctx: &Context, guild_id: GuildId) {
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    let channel_id = match manager.get(guild_id) {
+        Some(handler_lock) => match handler_lock.lock().await.current_channel() {
+            Some(channel_id) => ChannelId(channel_id.0),
+            None => return,
+        },
+        None => return,
+    };
+
+    let leave_when_alone = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        settings.guild(guild_id).is_none_or(|guild_settings| guild_settings.leave_when_alone)
+    };
+    if !leave_when_alone {
+        return;
+    }
+
+    match listeners(&ctx.cache, guild_id, channel_id).await {
+        Some(listeners) if listeners.is_empty() => {},
+        _ => return,
+    }
+
+    let player_states = player_states(ctx).await;
+    let text_channel_id = player_states.read().await.get(&guild_id).and_then(|player_state| player_state.text_channel_id);
+
+    // the session is kept, so it can still be resumed once someone is back
+    let result = leave_voice(&manager, &player_states, &sessions(ctx).await, guild_id, false).await;
+    if let Some(text_channel_id) = text_channel_id {
+        match result {
+            Ok(_) => send_channel_msg(&ctx.http, text_channel_id, "Left the voice channel since everyone else left").await,
+            Err(reason) => send_channel_msg(&ctx.http, text_channel_id, &format!("Failed: {:?}", reason)).await,
+        }
+    }
+}
+
+fn on_off_string(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+// shows when the bot leaves the voice channel in the current guild
+#[command]
+#[only_in(guilds)]
+#[sub_commands(idle_set, idle_alone, idle_warn)]
+async fn idle(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let default_idle_timeout = config::get(ctx).await.voice_idle_timeout;
+    let (idle_timeout, leave_when_alone, idle_warning) = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        match settings.guild(guild_id) {
+            Some(guild_settings) => (guild_settings.idle_timeout, guild_settings.leave_when_alone, guild_settings.idle_warning),
+            None => (None, true, false),
+        }
+    };
+
+    let timeout_string = match idle_timeout.unwrap_or(default_idle_timeout) {
+        0 => "never leaves due to inactivity".to_string(),
+        1 => "leaves after 1 minute without audio".to_string(),
+        minutes => format!("leaves after {} minutes without audio", minutes),
+    };
+    let default_string = if idle_timeout.is_none() { " (the default)" } else { "" };
+    send_msg(ctx, msg, &format!(
+        "In this server the bot {}{}\nLeaving when alone in the voice channel: {}\nWarning a minute before leaving: {}",
+        timeout_string, default_string, on_off_string(leave_when_alone), on_off_string(idle_warning),
+    )).await;
+
+    Ok(())
+}
+
+// sets how many minutes without audio the bot waits before leaving the voice channel
+#[command("set")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn idle_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let idle_timeout = match args.single::<String>().as_deref() {
+        Ok("never") => Some(0),
+        Ok("default") => None,
+        Ok(minutes) => match minutes.parse::<u64>() {
+            Ok(minutes) => Some(minutes),
+            Err(_) => {
+                send_msg(ctx, msg, "Usage: `idle set <minutes|never|default>`").await;
+                return Ok(());
+            },
+        },
+        Err(_) => {
+            send_msg(ctx, msg, "Usage: `idle set <minutes|never|default>`").await;
+            return Ok(());
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.guild_mut(guild_id).idle_timeout = idle_timeout;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    match idle_timeout {
+        Some(0) => send_msg(ctx, msg, "The bot will no longer leave due to inactivity").await,
+        Some(minutes) => send_msg(ctx, msg, &format!("The bot will leave after {} minutes without audio", minutes)).await,
+        None => send_msg(ctx, msg, &format!("Idle timeout reset to the default of {} minutes", config::get(ctx).await.voice_idle_timeout)).await,
+    }
+
+    Ok(())
+}
+
+// sets whether the bot leaves as soon as everyone else has left the voice channel
+#[command("alone")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn idle_alone(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let enabled = match parse_on_off(args) {
+        Some(enabled) => enabled,
+        None => {
+            send_msg(ctx, msg, "Usage: `idle alone on|off`").await;
+            return Ok(());
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.guild_mut(guild_id).leave_when_alone = enabled;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Leaving when alone in the voice channel turned {}", on_off_string(enabled))).await;
+
+    Ok(())
+}
+
+// sets whether the bot warns a minute before leaving due to inactivity
+#[command("warn")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn idle_warn(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let enabled = match parse_on_off(args) {
+        Some(enabled) => enabled,
+        None => {
+            send_msg(ctx, msg, "Usage: `idle warn on|off`").await;
+            return Ok(());
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.guild_mut(guild_id).idle_warning = enabled;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Warning before leaving due to inactivity turned {}", on_off_string(enabled))).await;
+
+    Ok(())
+}
+
+fn parse_on_off(mut args: Args) -> Option<bool> {
+    match args.single::<String>().as_deref() {
+        Ok("on") => Some(true),
+        Ok("off") => Some(false),
+        _ => None,
+    }
+}
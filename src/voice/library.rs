@@ -0,0 +1,326 @@
+use serde_json::Value;
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::channel::Message,
+    prelude::{Mutex, TypeMapKey},
+};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::process::Command;
+
+use super::{format_duration, MAX_PAGE_LENGTH, MAX_TITLE_LENGTH};
+use crate::{config, send_msg, shorten, Result, ResultExt};
+
+const ENTRIES_PER_PAGE: usize = 10;
+// the music directory is scanned again once the last scan is this old, or on `library refresh`
+const RESCAN_INTERVAL: Duration = Duration::from_secs(10 * 60);
+pub const AUDIO_EXTENSIONS: &[&str] = &["aac", "flac", "m4a", "mp3", "oga", "ogg", "opus", "wav", "webm", "wma"];
+
+pub struct LibraryContainer;
+impl TypeMapKey for LibraryContainer {
+    type Value = Arc<Mutex<Library>>;
+}
+
+// an audio file in the music directory
+#[derive(Clone)]
+pub struct LibraryEntry {
+    // relative to the music directory, always separated by `/`
+    pub name: String,
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl LibraryEntry {
+    pub fn display_title(&self) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => self.name.clone(),
+        }
+    }
+
+    fn matches(&self, terms: &[String]) -> bool {
+        let fields = [Some(&self.name), self.title.as_ref(), self.artist.as_ref(), self.album.as_ref()];
+        let haystack = fields.iter().flatten().map(|field| field.to_lowercase()).collect::<Vec<_>>().join("\n");
+        terms.iter().all(|term| haystack.contains(term.as_str()))
+    }
+}
+
+// the files found in the music directory, kept between scans so unchanged files aren't probed again
+#[derive(Default)]
+pub struct Library {
+    entries: HashMap<PathBuf, (SystemTime, LibraryEntry)>,
+    // every entry sorted by name, as of the last scan
+    sorted_entries: Vec<LibraryEntry>,
+    scanned_at: Option<Instant>,
+}
+
+impl Library {
+    // returns every entry sorted by name, scanning the music directory first if the last scan is outdated
+    pub async fn entries(&mut self, music_dir: &Path, rescan: bool) -> Result<Vec<LibraryEntry>> {
+        if rescan || self.outdated() {
+            self.refresh(music_dir).await?;
+        }
+
+        Ok(self.sorted_entries.clone())
+    }
+
+    fn outdated(&self) -> bool {
+        self.scanned_at.is_none_or(|scanned_at| scanned_at.elapsed() >= RESCAN_INTERVAL)
+    }
+
+    async fn refresh(&mut self, music_dir: &Path) -> Result<()> {
+        // walking the directory tree blocks, so it runs on a blocking thread
+        let dir = music_dir.to_path_buf();
+        let files = tokio::task::spawn_blocking(move || {
+            let mut files = Vec::new();
+            find_audio_files(&dir, &mut files).map(|_| files)
+        }).await.chain_err(|| "Scanning the music directory was interrupted")?
+            .chain_err(|| format!("Unable to read music directory {:?}", music_dir))?;
+
+        let mut entries = HashMap::new();
+        for (path, modified) in files {
+            let entry = match self.entries.remove(&path) {
+                Some((cached_modified, entry)) if cached_modified == modified => entry,
+                _ => probe_file(music_dir, &path).await,
+            };
+            entries.insert(path, (modified, entry));
+        }
+        self.entries = entries;
+
+        self.sorted_entries = self.entries.values().map(|(_, entry)| entry.clone()).collect();
+        self.sorted_entries.sort_by(|a, b| a.name.cmp(&b.name));
+        self.scanned_at = Some(Instant::now());
+
+        Ok(())
+    }
+}
+
+// collects the audio files below `dir` along with when they were last modified
+fn find_audio_files(dir: &Path, files: &mut Vec<(PathBuf, SystemTime)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // symlinked directories aren't followed, so a link loop can't recurse forever
+        if entry.file_type()?.is_dir() {
+            find_audio_files(&path, files)?;
+        } else if path.extension().and_then(|extension| extension.to_str())
+            .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str())) {
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((path, modified));
+        }
+    }
+
+    Ok(())
+}
+
+// reads the tags of a file with ffprobe, files that can't be probed are still listed by name
+async fn probe_file(music_dir: &Path, path: &Path) -> LibraryEntry {
+    let name = path.strip_prefix(music_dir).unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let output = Command::new("ffprobe")
+        .args(["-v", "quiet", "-of", "json", "-show_format", "-i"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .await;
+    let format = output.ok()
+        .and_then(|output| serde_json::from_slice::<Value>(&output.stdout).ok())
+        .and_then(|value| value.get("format").cloned())
+        .unwrap_or(Value::Null);
+
+    // tag names are uppercase in some containers, such as FLAC and Ogg
+    let tags = format.get("tags").and_then(Value::as_object)
+        .map(|tags| tags.iter()
+            .filter_map(|(key, value)| Some((key.to_lowercase(), value.as_str()?.to_string())))
+            .collect::<HashMap<_, _>>())
+        .unwrap_or_default();
+    let duration = format.get("duration").and_then(Value::as_str)
+        .and_then(|duration| duration.parse::<f64>().ok())
+        .filter(|duration| duration.is_finite() && *duration >= 0.0)
+        .map(Duration::from_secs_f64);
+
+    LibraryEntry {
+        name,
+        path: path.to_path_buf(),
+        title: tags.get("title").cloned(),
+        artist: tags.get("artist").cloned(),
+        album: tags.get("album").cloned(),
+        duration,
+    }
+}
+
+// lists the configured music directory, replying with an error if there isn't one
+async fn library_entries(ctx: &Context, msg: &Message, rescan: bool) -> Option<Vec<LibraryEntry>> {
+    let music_dir = match &config::get(ctx).await.music_dir {
+        Some(music_dir) => music_dir.clone(),
+        None => {
+            send_msg(ctx, msg, "No music library has been configured").await;
+            return None;
+        },
+    };
+
+    let library_lock = {
+        let data = ctx.data.read().await;
+        data.get::<LibraryContainer>().expect("Error getting music library").clone()
+    };
+    let mut library = library_lock.lock().await;
+
+    // probing new files can take a while
+    if rescan || library.outdated() {
+        let _ = msg.channel_id.broadcast_typing(&ctx.http).await;
+    }
+
+    match library.entries(&music_dir, rescan).await {
+        Ok(entries) => Some(entries),
+        Err(reason) => {
+            println!("Error reading music library: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error reading music library: {}", reason)).await;
+            None
+        },
+    }
+}
+
+// finds the library entry for `play file:<name>`, which is either its exact name or a unique search match
+pub async fn find_entry(ctx: &Context, msg: &Message, name: &str) -> Option<LibraryEntry> {
+    let entries = library_entries(ctx, msg, false).await?;
+
+    if let Some(entry) = entries.iter().find(|entry| entry.name == name) {
+        return Some(entry.clone());
+    }
+
+    let terms = search_terms(name);
+    let mut matches = entries.into_iter().filter(|entry| entry.matches(&terms)).collect::<Vec<_>>();
+    match matches.len() {
+        0 => {
+            send_msg(ctx, msg, &format!("No file in the music library matches `{}`", name)).await;
+            None
+        },
+        1 => matches.pop(),
+        match_count => {
+            let mut matches_string = format!("{} files in the music library match `{}`, use the full name of one:\n", match_count, name);
+            for entry in matches.iter().take(ENTRIES_PER_PAGE) {
+                matches_string.push_str(&format!("    `{}`\n", shorten(&entry.name, MAX_TITLE_LENGTH)));
+            }
+            send_msg(ctx, msg, &matches_string).await;
+            None
+        },
+    }
+}
+
+fn search_terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+async fn send_entries(ctx: &Context, msg: &Message, heading: &str, entries: &[LibraryEntry], page: usize) {
+    if entries.is_empty() {
+        send_msg(ctx, msg, &format!("{}: no files found", heading)).await;
+        return;
+    }
+
+    let page_count = entries.len().div_ceil(ENTRIES_PER_PAGE);
+    let page = page.clamp(1, page_count);
+
+    let mut entries_string = format!("{} (page {}/{}, {} files):\n", heading, page, page_count, entries.len());
+    for entry in entries.iter().skip((page - 1) * ENTRIES_PER_PAGE).take(ENTRIES_PER_PAGE) {
+        let duration_string = entry.duration.map(format_duration).unwrap_or_else(|| "unknown duration".to_string());
+        let line = format!("    {} ({}): `{}`\n", shorten(&entry.display_title(), MAX_TITLE_LENGTH), duration_string, entry.name);
+        if entries_string.len() + line.len() > MAX_PAGE_LENGTH {
+            entries_string.push_str("    ...\n");
+            break;
+        }
+        entries_string.push_str(&line);
+    }
+    entries_string.push_str("Play a file with `play file:<name>`");
+    send_msg(ctx, msg, &entries_string).await;
+}
+
+// lists the audio files in the music library, 10 per page
+#[command]
+#[aliases(lib)]
+#[sub_commands(library_search, library_refresh)]
+async fn library(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let page = args.single::<usize>().unwrap_or(1);
+
+    if let Some(entries) = library_entries(ctx, msg, false).await {
+        send_entries(ctx, msg, "Music library", &entries, page).await;
+    }
+
+    Ok(())
+}
+
+// lists the audio files whose name or tags contain every search term, 10 per page
+#[command("search")]
+async fn library_search(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut terms = search_terms(args.rest());
+    // the page is passed as `page:<n>`, since a plain number could be one of the search terms
+    let page = match terms.last().and_then(|term| term.strip_prefix("page:")).and_then(|page| page.parse::<usize>().ok()) {
+        Some(page) => {
+            terms.pop();
+            page
+        },
+        None => 1,
+    };
+    if terms.is_empty() {
+        send_msg(ctx, msg, "Usage: `library search <terms> [page:<n>]`").await;
+        return Ok(());
+    }
+
+    if let Some(entries) = library_entries(ctx, msg, false).await {
+        let matches = entries.into_iter().filter(|entry| entry.matches(&terms)).collect::<Vec<_>>();
+        send_entries(ctx, msg, "Matching files", &matches, page).await;
+    }
+
+    Ok(())
+}
+
+// scans the music directory again right away, instead of waiting for the cached scan to expire
+#[command("refresh")]
+#[owners_only]
+async fn library_refresh(ctx: &Context, msg: &Message) -> CommandResult {
+    if let Some(entries) = library_entries(ctx, msg, true).await {
+        send_msg(ctx, msg, &format!("Found {} files in the music library", entries.len())).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn audio_files_are_found_without_following_symlinked_directories() {
+        let dir = env::temp_dir().join(format!("rybot2-library-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("album")).unwrap();
+        fs::write(dir.join("album").join("track.MP3"), b"").unwrap();
+        fs::write(dir.join("cover.jpg"), b"").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("album").join("loop")).unwrap();
+
+        let mut files = Vec::new();
+        find_audio_files(&dir, &mut files).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(files.into_iter().map(|(path, _)| path).collect::<Vec<_>>(), vec![dir.join("album").join("track.MP3")]);
+    }
+}
@@ -0,0 +1,57 @@
+use serenity::model::prelude::UserId;
+use songbird::Call;
+use std::{fmt, time::Duration};
+
+use super::{format_duration, track_request};
+use crate::config::Config;
+
+// why a track can't be queued
+pub enum LimitError {
+    TooLong { duration: Duration, max_duration: Duration },
+    UserQueueFull(usize),
+    QueueFull(usize),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooLong { duration, max_duration } => write!(f, "Tracks can be at most {} long, this one is {}", format_duration(*max_duration), format_duration(*duration)),
+            LimitError::UserQueueFull(max_tracks) => write!(f, "You already have the maximum of {} tracks in the queue", max_tracks),
+            LimitError::QueueFull(max_tracks) => write!(f, "The queue is full, it can hold at most {} tracks", max_tracks),
+        }
+    }
+}
+
+// checks whether the queue has room for another track from the user, which is known before the track is started
+pub async fn check_queue_limits(config: &Config, handler: &Call, user_id: UserId) -> Result<(), LimitError> {
+    let tracks = handler.queue().current_queue();
+    if config.max_queue_length > 0 && tracks.len() >= config.max_queue_length {
+        return Err(LimitError::QueueFull(config.max_queue_length));
+    }
+
+    if config.max_user_tracks > 0 {
+        let mut user_tracks = 0;
+        for track in &tracks {
+            if track_request(track).await.is_some_and(|request| request.user_id == user_id) {
+                user_tracks += 1;
+            }
+        }
+        if user_tracks >= config.max_user_tracks {
+            return Err(LimitError::UserQueueFull(config.max_user_tracks));
+        }
+    }
+
+    Ok(())
+}
+
+// checks the duration of a started track, tracks of unknown length such as livestreams are let through
+pub fn check_duration(config: &Config, duration: Option<Duration>) -> Result<(), LimitError> {
+    if config.max_track_duration > 0 {
+        let max_duration = Duration::from_secs(config.max_track_duration * 60);
+        if let Some(duration) = duration.filter(|duration| *duration > max_duration) {
+            return Err(LimitError::TooLong { duration, max_duration });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::{channel::Message, prelude::GuildId},
+};
+use songbird::{tracks::{LoopState, PlayMode}, Event, EventContext, EventHandler as VoiceEventHandler, Songbird};
+use std::{fmt, sync::Arc};
+
+use super::{dj_role_string, enqueue, filter::{recreate_input, set_track_filter}, is_dj, player_states, track_request, PlayerStates};
+use crate::send_msg;
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoopMode {
+    #[default]
+    Off,
+    // the current track repeats until it's skipped
+    Track,
+    // tracks that finish playing are queued again
+    Queue,
+}
+
+impl fmt::Display for LoopMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopMode::Off => f.write_str("off"),
+            LoopMode::Track => f.write_str("track"),
+            LoopMode::Queue => f.write_str("queue"),
+        }
+    }
+}
+
+// shows or sets the loop mode of the guild
+#[command("loop")]
+#[aliases(repeat)]
+#[only_in(guilds)]
+async fn loop_mode(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let player_states = player_states(ctx).await;
+
+    let loop_mode = match args.single::<String>().as_deref() {
+        Ok("track") | Ok("song") => LoopMode::Track,
+        Ok("queue") | Ok("all") => LoopMode::Queue,
+        Ok("off") => LoopMode::Off,
+        Ok(_) => {
+            send_msg(ctx, msg, "Usage: `loop track|queue|off`").await;
+            return Ok(());
+        },
+        Err(_) => {
+            let loop_mode = player_states.read().await.get(&guild_id).map(|player_state| player_state.loop_mode).unwrap_or_default();
+            send_msg(ctx, msg, &format!("Loop mode is {}", loop_mode)).await;
+            return Ok(());
+        },
+    };
+
+    // anyone can see the loop mode, but changing it is up to DJs
+    if !is_dj(ctx, msg).await {
+        send_msg(ctx, msg, &format!("Only members with {} can change the loop mode", dj_role_string(ctx, msg).await)).await;
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        let current_track = handler.queue().current();
+        if loop_mode == LoopMode::Track {
+            let current_track = match current_track {
+                Some(current_track) => current_track,
+                None => {
+                    send_msg(ctx, msg, "No audio track appears to be playing at the moment").await;
+                    return Ok(());
+                }
+            };
+            if let Err(reason) = current_track.enable_loop() {
+                send_msg(ctx, msg, &format!("Error looping audio: {:?}", reason)).await;
+                return Ok(());
+            }
+        } else if let Some(current_track) = current_track {
+            let _ = current_track.disable_loop();
+        }
+
+        player_states.write().await.entry(guild_id).or_default().loop_mode = loop_mode;
+
+        send_msg(ctx, msg, &format!("Loop mode set to {}", loop_mode)).await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// called whenever a track ends or is stopped, to apply the guild's loop mode
+pub struct TrackEndNotifier {
+    pub manager: Arc<Songbird>,
+    pub guild_id: GuildId,
+    pub player_states: PlayerStates,
+}
+
+#[async_trait]
+impl VoiceEventHandler for TrackEndNotifier {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let track_list = match ctx {
+            EventContext::Track(track_list) => track_list,
+            _ => return None,
+        };
+
+        let loop_mode = self.player_states.read().await.get(&self.guild_id).map(|player_state| player_state.loop_mode).unwrap_or_default();
+
+        for (track_state, track_handle) in track_list.iter() {
+            // tracks without a request weren't queued, so they aren't affected by the loop mode
+            let request = match track_request(track_handle).await {
+                Some(request) => request,
+                None => continue,
+            };
+
+            match loop_mode {
+                LoopMode::Track if track_state.loops == LoopState::Infinite => {
+                    // a looping track only ends when it's skipped or stopped, which also ends the loop
+                    if let Some(player_state) = self.player_states.write().await.get_mut(&self.guild_id) {
+                        player_state.loop_mode = LoopMode::Off;
+                    }
+                },
+                // skipped and removed tracks are stopped rather than ended, so they leave the loop
+                LoopMode::Queue if track_state.playing == PlayMode::End => {
+                    let (input, filter) = match recreate_input(track_handle, &request.source).await {
+                        Ok(input) => input,
+                        Err(reason) => {
+                            println!("Error requeuing source: {:?}", reason);
+                            continue;
+                        },
+                    };
+                    if let Some(handler_lock) = self.manager.get(self.guild_id) {
+                        let mut handler = handler_lock.lock().await;
+                        let requeued_track = enqueue(&mut handler, input, request, track_state.volume).await;
+                        set_track_filter(&requeued_track, filter).await;
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        None
+    }
+}
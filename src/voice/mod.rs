@@ -0,0 +1,705 @@
+use serenity::{
+    async_trait,
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::{command, group},
+    },
+    http::Http,
+    model::{
+        channel::{Attachment, Message},
+        misc::Mentionable,
+        prelude::{ChannelId, GuildId},
+    },
+    prelude::{Mutex, RwLock, TypeMapKey},
+};
+use songbird::{error::JoinResult, tracks::PlayMode, Call, Event, EventContext, EventHandler as VoiceEventHandler, Songbird, TrackEvent};
+use std::{collections::HashMap, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::Duration};
+
+use crate::{config, send_channel_msg, send_msg, settings::{self, Settings}};
+
+mod dj;
+mod filter;
+mod idle;
+mod library;
+mod limits;
+mod looping;
+mod playback;
+mod playlist;
+mod queue;
+mod search;
+mod session;
+mod soundboard;
+mod track;
+mod voteskip;
+
+pub use idle::leave_if_alone;
+pub use library::LibraryContainer;
+pub use looping::LoopMode;
+pub use session::{offer_restore as offer_session_restore, SessionContainer, SessionStore, SESSIONS_FILE_NAME};
+pub use track::{enqueue, track_request, track_title, TrackRequest, TrackSource};
+
+use dj::{dj_role_string, is_dj, DJ_CHECK, DJ_COMMAND, REQUESTER_CHECK};
+use filter::{track_filter, Filter, FILTER_COMMAND};
+use idle::IDLE_COMMAND;
+use library::LIBRARY_COMMAND;
+use looping::{TrackEndNotifier, LOOP_MODE_COMMAND};
+use playback::{PAUSE_COMMAND, RESUME_COMMAND, SEEK_COMMAND, VOLUME_COMMAND};
+use playlist::PlaylistEntry;
+use queue::{CLEAR_COMMAND, MOVE_TRACK_COMMAND, QUEUE_COMMAND, REMOVE_COMMAND, SHUFFLE_COMMAND};
+use search::SEARCH_COMMAND;
+use session::{sessions, SessionNotifier, Sessions, SESSION_COMMAND};
+use soundboard::SB_COMMAND;
+use voteskip::VOTESKIP_COMMAND;
+
+#[group]
+#[commands(join, leave, play, skip, voteskip, stop, np, queue, remove, move_track, shuffle, clear, pause, resume, volume, seek, filter, loop_mode, search, library, sb, idle, session, dj)]
+pub struct Voice;
+
+// per-guild playback state that only lasts while the bot is running
+#[derive(Default)]
+pub struct PlayerState {
+    pub loop_mode: LoopMode,
+    // the text channel the bot was last asked to join from
+    pub text_channel_id: Option<ChannelId>,
+}
+
+pub type PlayerStates = Arc<RwLock<HashMap<GuildId, PlayerState>>>;
+
+pub struct PlayerStateContainer;
+impl TypeMapKey for PlayerStateContainer {
+    type Value = PlayerStates;
+}
+
+// long titles are shortened so listings stay below Discord's limit of 2000 characters per message
+const MAX_TITLE_LENGTH: usize = 100;
+const MAX_PAGE_LENGTH: usize = 1900;
+
+pub async fn player_states(ctx: &Context) -> PlayerStates {
+    let data = ctx.data.read().await;
+    data.get::<PlayerStateContainer>().expect("Error getting player states").clone()
+}
+
+// formats a duration as `m:ss`, or `h:mm:ss` if it's at least an hour long
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+// draws a bar with a knob at the current position, like `▬▬▬🔘▬▬▬▬▬▬`
+pub fn progress_bar(position: Duration, duration: Duration) -> String {
+    const LENGTH: usize = 15;
+    let knob = if duration.as_millis() == 0 {
+        0
+    } else {
+        ((position.as_millis() * LENGTH as u128 / duration.as_millis()) as usize).min(LENGTH - 1)
+    };
+
+    (0..LENGTH).map(|index| if index == knob { "🔘" } else { "▬" }).collect()
+}
+
+// parses `ss`, `mm:ss` or `hh:mm:ss` into a duration
+pub fn parse_duration(string: &str) -> Option<Duration> {
+    let mut seconds: u64 = 0;
+    for (index, part) in string.split(':').enumerate() {
+        let value = part.parse::<u64>().ok()?;
+        if index > 2 || (index > 0 && value >= 60) {
+            return None;
+        }
+        // a huge first field would overflow
+        seconds = seconds.checked_mul(60)?.checked_add(value)?;
+    }
+
+    Some(Duration::from_secs(seconds))
+}
+
+async fn join_impl(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let channel_id = guild.voice_states.get(&msg.author.id).and_then(|voice_state| voice_state.channel_id);
+
+    let connect_to = match channel_id {
+        Some(channel) => channel,
+        None => {
+            send_msg(ctx, msg, "Not in a voice channel").await;
+
+            return Ok(());
+        }
+    };
+
+    // moving the call takes the whole queue along, so only DJs can pull the bot away from its listeners
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+    let current_channel = match manager.get(guild_id) {
+        Some(handler_lock) => handler_lock.lock().await.current_channel(),
+        None => None,
+    };
+    if current_channel.is_some_and(|channel_id| channel_id.0 != connect_to.0) && !is_dj(ctx, msg).await {
+        send_msg(ctx, msg, &format!("Only members with {} can move the bot to another voice channel", dj_role_string(ctx, msg).await)).await;
+        return Ok(());
+    }
+
+    if join_channel(ctx, guild_id, connect_to, Some(msg.channel_id)).await.is_ok() {
+        send_msg(ctx, msg, &format!("Joined {}", connect_to.mention())).await;
+    } else {
+        send_msg(ctx, msg, "Failed to join voice channel").await;
+    }
+
+    Ok(())
+}
+
+// joins a voice channel and sets up the events of a new call, messages about the call go to `text_channel_id` if there is one
+async fn join_channel(ctx: &Context, guild_id: GuildId, voice_channel_id: ChannelId, text_channel_id: Option<ChannelId>) -> JoinResult<()> {
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+    // moving to another channel keeps the existing call, along with its events
+    let already_connected = manager.get(guild_id).is_some();
+
+    let (handle_lock, success) = manager.join(guild_id, voice_channel_id).await;
+    success?;
+
+    // messages about leaving the channel go wherever the bot was last asked to join
+    player_states(ctx).await.write().await.entry(guild_id).or_default().text_channel_id = text_channel_id;
+    if already_connected {
+        return Ok(());
+    }
+
+    // once this call is gone, the guild can be offered to resume its session again
+    sessions(ctx).await.lock().await.forget_offer(guild_id);
+
+    let mut handle = handle_lock.lock().await;
+    handle.add_global_event(
+        Event::Track(TrackEvent::End),
+        TrackEndNotifier {
+            manager: manager.clone(),
+            guild_id,
+            player_states: player_states(ctx).await,
+        },
+    );
+
+    handle.add_global_event(
+        Event::Periodic(Duration::from_secs(15), None),
+        SessionNotifier {
+            manager: manager.clone(),
+            guild_id,
+            player_states: player_states(ctx).await,
+            sessions: sessions(ctx).await,
+            saved: Default::default(),
+        },
+    );
+
+    handle.add_global_event(
+        Event::Periodic(Duration::from_secs(60), None),
+        ChannelDurationNotifier {
+            count: Default::default(),
+            default_idle_timeout: config::get(ctx).await.voice_idle_timeout,
+            http: ctx.http.clone(),
+            manager,
+            guild_id,
+            player_states: player_states(ctx).await,
+            sessions: sessions(ctx).await,
+            settings: settings::get(ctx).await,
+        },
+    );
+
+    Ok(())
+}
+
+struct ChannelDurationNotifier {
+    count: Arc<AtomicUsize>,
+    // in minutes, used if the guild hasn't set its own idle timeout
+    default_idle_timeout: u64,
+    http: Arc<Http>,
+
+    manager: Arc<Songbird>,
+    guild_id: GuildId,
+    player_states: PlayerStates,
+    sessions: Sessions,
+    settings: Arc<RwLock<Settings>>,
+}
+
+// TODO: change `if let Some(handler_lock)` to `if let Some(handler)` and change `let handler = handler_lock.lock().await;` to `let handler_lock = handler.lock().await;`
+//       this should be changed throughout the entire file
+
+#[async_trait]
+impl VoiceEventHandler for ChannelDurationNotifier {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        let mut active = false;
+        if let Some(handler_lock) = self.manager.get(self.guild_id) {
+            let handler = handler_lock.lock().await;
+            active = handler.queue().current().is_some();
+        }
+
+        let count = if active {
+            // the audio queue isn't empty, so set the number of minutes since last inactive to 0
+            self.count.store(0, Ordering::Relaxed);
+            return None;
+        } else {
+            self.count.fetch_add(1, Ordering::Relaxed) + 1
+        };
+
+        let (idle_timeout, idle_warning) = match self.settings.read().await.guild(self.guild_id) {
+            Some(guild_settings) => (guild_settings.idle_timeout.unwrap_or(self.default_idle_timeout), guild_settings.idle_warning),
+            None => (self.default_idle_timeout, false),
+        };
+        let idle_timeout = idle_timeout as usize;
+        if idle_timeout == 0 {
+            return None;
+        }
+
+        // messages go wherever the bot was last asked to join, like when it leaves for being alone
+        let text_channel_id = self.player_states.read().await.get(&self.guild_id).and_then(|player_state| player_state.text_channel_id);
+
+        if idle_warning && idle_timeout > 1 && count + 1 == idle_timeout {
+            if let Some(text_channel_id) = text_channel_id {
+                send_channel_msg(&self.http, text_channel_id, "Leaving the voice channel in a minute due to inactivity").await;
+            }
+        }
+
+        if count >= idle_timeout {
+            // the idle timeout has passed since the audio queue last contained anything, so leave the VC
+            let result = leave_voice(&self.manager, &self.player_states, &self.sessions, self.guild_id, false).await;
+            if let Some(text_channel_id) = text_channel_id {
+                match result {
+                    Ok(_) => send_channel_msg(&self.http, text_channel_id, "Left the voice channel due to inactivity").await,
+                    Err(reason) => send_channel_msg(&self.http, text_channel_id, &format!("Failed: {:?}", reason)).await,
+                }
+            }
+            self.count.store(0, Ordering::Relaxed);
+        }
+
+        None
+    }
+}
+
+// leaves the guild's voice channel and forgets its playback state, and its saved session if `forget_session` is set
+pub async fn leave_voice(manager: &Songbird, player_states: &PlayerStates, sessions: &Sessions, guild_id: GuildId, forget_session: bool) -> JoinResult<()> {
+    player_states.write().await.remove(&guild_id);
+    if forget_session {
+        if let Err(reason) = sessions.lock().await.set(guild_id, None) {
+            println!("Error saving voice session: {:?}", reason);
+        }
+    }
+    manager.remove(guild_id).await
+}
+
+// joins the voice channel that the requesting user is currently in
+#[command]
+#[only_in(guilds)]
+async fn join(ctx: &Context, msg: &Message) -> CommandResult {
+    join_impl(ctx, msg).await
+}
+
+// leaves the current voice channel
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+    let has_handler = manager.get(guild_id).is_some();
+
+    if has_handler {
+        if let Err(reason) = leave_voice(&manager, &player_states(ctx).await, &sessions(ctx).await, guild_id, true).await {
+            send_msg(ctx, msg, format!("Failed: {:?}", reason).as_str()).await;
+        }
+
+        send_msg(ctx, msg, "Left the voice channel").await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+
+// the audio files attached to a message, or to the message it replies to
+fn audio_attachments(msg: &Message) -> Vec<&Attachment> {
+    let is_audio = |attachment: &&Attachment| {
+        let audio_content_type = attachment.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("audio/"));
+        let audio_extension = attachment.filename.rsplit_once('.')
+            .is_some_and(|(_, extension)| library::AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+        audio_content_type || audio_extension
+    };
+
+    let attachments = msg.attachments.iter().filter(is_audio).collect::<Vec<_>>();
+    if !attachments.is_empty() {
+        return attachments;
+    }
+
+    match &msg.referenced_message {
+        Some(referenced_message) => referenced_message.attachments.iter().filter(is_audio).collect(),
+        None => Vec::new(),
+    }
+}
+
+// starts a source and adds it to the queue, replying with its URL or title
+async fn queue_source(ctx: &Context, msg: &Message, handler_lock: &Mutex<Call>, source: TrackSource, volume: u32) {
+    let config = config::get(ctx).await;
+
+    // a full queue is turned down before youtube-dl is started for nothing
+    if let Err(reason) = limits::check_queue_limits(&config, &*handler_lock.lock().await, msg.author.id).await {
+        send_msg(ctx, msg, &reason.to_string()).await;
+        return;
+    }
+
+    // starting a source can take a while, so the call isn't locked meanwhile
+    let input = match source.create_input().await {
+        Ok(input) => input,
+        Err(reason) => {
+            println!("Error starting source: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error starting source: {:?}", reason)).await;
+            return;
+        },
+    };
+
+    if let Err(reason) = limits::check_duration(&config, input.metadata.duration) {
+        send_msg(ctx, msg, &reason.to_string()).await;
+        return;
+    }
+
+    let mut handler = handler_lock.lock().await;
+    // other tracks may have been queued while this one was starting
+    if let Err(reason) = limits::check_queue_limits(&config, &handler, msg.author.id).await {
+        send_msg(ctx, msg, &reason.to_string()).await;
+        return;
+    }
+
+    // searches are pinned to the video they found, so requeuing the track plays the same video
+    let source = match (source, &input.metadata.source_url) {
+        (TrackSource::Ytdl(_), Some(source_url)) => TrackSource::Ytdl(source_url.clone()),
+        (source, _) => source,
+    };
+
+    {
+        let source_url_option = input.metadata.source_url.clone();
+        let source_url = source_url_option.unwrap_or("Unable to extract source URL".to_string());
+        let queue_or_play = if handler.queue().is_empty() { "Playing" } else { "Queuing" };
+        send_msg(ctx, msg, &format!("{} audio ({})", queue_or_play, source_url)).await;
+    }
+
+    enqueue(&mut handler, input, TrackRequest { user_id: msg.author.id, requested_at: msg.timestamp, source }, volume as f32 / 100.0).await;
+}
+
+// plays audio from requested URL in the current voice channel
+#[command]
+#[only_in(guilds)]
+async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let url_or_search = args.rest();
+    let mut should_search = false;
+    if !url_or_search.starts_with("http") {
+        //send_msg(&ctx, &msg, "Must provide a valid URL").await;
+        //return Ok(());
+        should_search = true;
+    }
+
+    // audio attached to the command, or to the message it replies to, is played when nothing else is requested
+    let attachments = if url_or_search.is_empty() { audio_attachments(msg) } else { Vec::new() };
+    if url_or_search.is_empty() && attachments.is_empty() {
+        send_msg(ctx, msg, "Usage: `play <url|search terms|file:name>`, or attach an audio file").await;
+        return Ok(());
+    }
+
+    // `file:<name>` plays from the music library instead of through youtube-dl
+    let library_entry = match url_or_search.strip_prefix("file:") {
+        Some(name) => match library::find_entry(ctx, msg, name.trim()).await {
+            Some(library_entry) => Some(library_entry),
+            None => return Ok(()),
+        },
+        None => None,
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    let handler_option = manager.get(guild_id);
+    if handler_option.is_none() {
+        let _ = join_impl(ctx, msg).await;
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let volume = {
+            let settings_lock = settings::get(ctx).await;
+            let settings = settings_lock.read().await;
+            settings.guild(guild_id).map_or(settings::DEFAULT_VOLUME, |guild_settings| guild_settings.volume)
+        };
+
+        if !attachments.is_empty() {
+            for attachment in attachments {
+                let source = TrackSource::Url { url: attachment.url.clone(), title: attachment.filename.clone() };
+                queue_source(ctx, msg, &handler_lock, source, volume).await;
+            }
+            return Ok(());
+        }
+
+        if !should_search && playlist::is_playlist_url(url_or_search) {
+            match playlist::fetch_playlist(url_or_search).await {
+                Ok(Some(playlist)) => {
+                    let config = config::get(ctx).await;
+                    let max_tracks = config.max_playlist_tracks;
+                    let entry_count = playlist.entries.len();
+                    if entry_count == 0 {
+                        send_msg(ctx, msg, "The playlist doesn't contain any playable tracks").await;
+                        return Ok(());
+                    }
+
+                    let mut queued = 0;
+                    let mut too_long = 0;
+                    let mut full_reason = None;
+                    for source in playlist.entries.into_iter().take(max_tracks).map(PlaylistEntry::into_source) {
+                        if let Err(reason) = limits::check_queue_limits(&config, &*handler_lock.lock().await, msg.author.id).await {
+                            full_reason = Some(reason);
+                            break;
+                        }
+                        // lazy entries only fail here if they're malformed, youtube-dl isn't run until they play
+                        let input = match source.create_input().await {
+                            Ok(input) => input,
+                            Err(reason) => {
+                                println!("Error starting source: {:?}", reason);
+                                continue;
+                            },
+                        };
+                        if limits::check_duration(&config, input.metadata.duration).is_err() {
+                            too_long += 1;
+                            continue;
+                        }
+
+                        let mut handler = handler_lock.lock().await;
+                        // other tracks may have been queued while this one was starting
+                        if let Err(reason) = limits::check_queue_limits(&config, &handler, msg.author.id).await {
+                            full_reason = Some(reason);
+                            break;
+                        }
+                        enqueue(&mut handler, input, TrackRequest { user_id: msg.author.id, requested_at: msg.timestamp, source }, volume as f32 / 100.0).await;
+                        queued += 1;
+                    }
+
+                    let mut queued_string = format!("Queuing {} tracks from {}", queued, playlist.title.unwrap_or_else(|| "the playlist".to_string()));
+                    if entry_count > max_tracks {
+                        queued_string.push_str(&format!(" (only the first {} of {} tracks can be queued at once)", max_tracks, entry_count));
+                    }
+                    if too_long > 0 {
+                        queued_string.push_str(&format!("\nSkipped {} tracks longer than {} minutes", too_long, config.max_track_duration));
+                    }
+                    if let Some(reason) = full_reason {
+                        queued_string.push_str(&format!("\nStopped queuing: {}", reason));
+                    }
+                    send_msg(ctx, msg, &queued_string).await;
+                    return Ok(());
+                },
+                Ok(None) => {},
+                Err(reason) => {
+                    println!("Error reading playlist: {:?}", reason);
+                    send_msg(ctx, msg, &format!("Error reading playlist: {}", reason)).await;
+                    return Ok(());
+                },
+            }
+        }
+
+        let source = if let Some(library_entry) = library_entry {
+            TrackSource::File(library_entry.path)
+        } else if should_search {
+            TrackSource::Ytdl(format!("ytsearch1:{}", url_or_search))
+        } else {
+            TrackSource::Ytdl(url_or_search.to_string())
+        };
+
+        queue_source(ctx, msg, &handler_lock, source, volume).await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// skips currently playing audio
+#[command]
+#[only_in(guilds)]
+#[checks(Requester)]
+async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        match handler.queue().skip() {
+            Ok(_) => {},
+            Err(reason) => send_msg(ctx, msg, &format!("Error skipping audio: {:?}", reason)).await,
+        }
+
+        send_msg(ctx, msg, "Skipped audio").await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// stops all audio playback
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn stop(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        handler.queue().stop();
+
+        send_msg(ctx, msg, "Stopped audio playback").await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// sends current audio playback info
+#[command]
+#[only_in(guilds)]
+async fn np(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let (current_track, queue_length) = {
+            let handler = handler_lock.lock().await;
+            (handler.queue().current(), handler.queue().len())
+        };
+        let current_track = match current_track {
+            Some(current_track) => current_track,
+            None => {
+                send_msg(ctx, msg, "No audio track appears to be playing at the moment").await;
+                return Ok(());
+            }
+        };
+
+        let metadata = current_track.metadata().clone();
+        let request = track_request(&current_track).await;
+        let track_state = current_track.get_info().await.ok();
+        let filter = track_filter(&current_track).await;
+        let loop_mode = player_states(ctx).await.read().await.get(&guild_id).map(|player_state| player_state.loop_mode).unwrap_or_default();
+
+        let progress_string = match &track_state {
+            Some(track_state) => {
+                let play_mode_emoji = if track_state.playing == PlayMode::Pause { "⏸" } else { "▶" };
+                match metadata.duration {
+                    Some(duration) => format!("{} {} {} / {}", play_mode_emoji, progress_bar(track_state.position, duration), format_duration(track_state.position), format_duration(duration)),
+                    None => format!("{} {} / unknown duration", play_mode_emoji, format_duration(track_state.position)),
+                }
+            },
+            None => "Unable to get the playback position".to_string(),
+        };
+        let artist_string = metadata.artist.clone()
+            .or_else(|| metadata.channel.clone())
+            .unwrap_or_else(|| "none".to_string());
+        let queue_string = match queue_length.saturating_sub(1) {
+            0 => "Nothing queued after this".to_string(),
+            1 => "1 track up next".to_string(),
+            up_next => format!("{} tracks up next", up_next),
+        };
+
+        if let Err(reason) = msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title(track_title(&current_track));
+                if let Some(source_url) = &metadata.source_url {
+                    e.url(source_url);
+                }
+                if let Some(thumbnail) = &metadata.thumbnail {
+                    e.thumbnail(thumbnail);
+                }
+                e.description(progress_string);
+                e.field("Artist", artist_string, true);
+                if let Some(track) = &metadata.track {
+                    e.field("Track", track, true);
+                }
+                e.field("Queue", queue_string, true);
+                e.field("Loop", loop_mode, true);
+                if filter != Filter::Off {
+                    e.field("Filter", filter, true);
+                }
+                if let Some(request) = &request {
+                    e.field("Requested by", request.user_id.mention(), true);
+                    e.footer(|f| f.text("Requested"));
+                    e.timestamp(request.requested_at);
+                }
+                e
+            })
+        }).await {
+            println!("Error sending message: {:?}", reason);
+        }
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_accepts_seconds_minutes_and_hours() {
+        assert_eq!(parse_duration("0"), Some(Duration::from_secs(0)));
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1:30"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("0:59"), Some(Duration::from_secs(59)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_input() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1:60"), None);
+        assert_eq!(parse_duration("1:00:60"), None);
+        assert_eq!(parse_duration("1:2:3:4"), None);
+        assert_eq!(parse_duration("-1"), None);
+        assert_eq!(parse_duration("1:"), None);
+        assert_eq!(parse_duration("a:00"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflowing_input() {
+        assert_eq!(parse_duration("99999999999999999999:00"), None);
+        assert_eq!(parse_duration("999999999999999999:00"), None);
+        assert_eq!(parse_duration(&format!("{}:00:00", u64::MAX / 3600 + 1)), None);
+        assert_eq!(parse_duration(&u64::MAX.to_string()), Some(Duration::from_secs(u64::MAX)));
+    }
+
+    #[test]
+    fn format_duration_switches_to_hours_at_one_hour() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0:00");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "0:59");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59:59");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(format_duration(Duration::from_secs(36_061)), "10:01:01");
+    }
+
+    #[test]
+    fn progress_bar_places_the_knob_by_position() {
+        let knob = |bar: String| bar.chars().position(|c| c == '🔘');
+        assert_eq!(knob(progress_bar(Duration::from_secs(0), Duration::from_secs(100))), Some(0));
+        assert_eq!(knob(progress_bar(Duration::from_secs(50), Duration::from_secs(100))), Some(7));
+        assert_eq!(knob(progress_bar(Duration::from_secs(100), Duration::from_secs(100))), Some(14));
+        assert_eq!(knob(progress_bar(Duration::from_secs(500), Duration::from_secs(100))), Some(14));
+        assert_eq!(knob(progress_bar(Duration::from_secs(5), Duration::from_secs(0))), Some(0));
+        assert_eq!(progress_bar(Duration::from_secs(1), Duration::from_secs(2)).chars().count(), 15);
+    }
+}
@@ -0,0 +1,166 @@
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::channel::Message,
+};
+
+use super::{dj_role_string, format_duration, is_dj, parse_duration, DJ_CHECK};
+use crate::{send_msg, settings};
+
+const MAX_VOLUME: u32 = 200;
+
+// pauses the current track
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        match handler.queue().pause() {
+            Ok(_) => send_msg(ctx, msg, "Paused audio").await,
+            Err(reason) => send_msg(ctx, msg, &format!("Error pausing audio: {:?}", reason)).await,
+        }
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// resumes the current track after it was paused
+#[command]
+#[aliases(unpause)]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        match handler.queue().resume() {
+            Ok(_) => send_msg(ctx, msg, "Resumed audio").await,
+            Err(reason) => send_msg(ctx, msg, &format!("Error resuming audio: {:?}", reason)).await,
+        }
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// shows the volume, or sets it for every queued track and remembers it for the guild
+#[command]
+#[aliases(vol)]
+#[only_in(guilds)]
+async fn volume(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let settings_lock = settings::get(ctx).await;
+
+    if args.is_empty() {
+        let volume = settings_lock.read().await.guild(guild_id).map_or(settings::DEFAULT_VOLUME, |guild_settings| guild_settings.volume);
+        send_msg(ctx, msg, &format!("Volume is {}%", volume)).await;
+        return Ok(());
+    }
+
+    let volume = match args.single::<u32>() {
+        Ok(volume) if volume <= MAX_VOLUME => volume,
+        _ => {
+            send_msg(ctx, msg, &format!("Usage: `volume <0-{}>`", MAX_VOLUME)).await;
+            return Ok(());
+        },
+    };
+
+    // anyone can see the volume, but changing it is up to DJs
+    if !is_dj(ctx, msg).await {
+        send_msg(ctx, msg, &format!("Only members with {} can change the volume", dj_role_string(ctx, msg).await)).await;
+        return Ok(());
+    }
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        for track in handler.queue().current_queue() {
+            let _ = track.set_volume(volume as f32 / 100.0);
+        }
+    }
+
+    let mut settings = settings_lock.write().await;
+    settings.guild_mut(guild_id).volume = volume;
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Volume set to {}%", volume)).await;
+
+    Ok(())
+}
+
+// jumps to the specified position in the current track
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn seek(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let position = match parse_duration(args.rest().trim()) {
+        Some(position) => position,
+        None => {
+            send_msg(ctx, msg, "Usage: `seek <mm:ss>`").await;
+            return Ok(());
+        },
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        let current_track = match handler.queue().current() {
+            Some(current_track) => current_track,
+            None => {
+                send_msg(ctx, msg, "No audio track appears to be playing at the moment").await;
+                return Ok(());
+            }
+        };
+
+        if !current_track.is_seekable() {
+            send_msg(ctx, msg, "The current audio track can't be seeked").await;
+            return Ok(());
+        }
+        if let Some(duration) = current_track.metadata().duration {
+            if position >= duration {
+                send_msg(ctx, msg, &format!("The current audio track is only {} long", format_duration(duration))).await;
+                return Ok(());
+            }
+        }
+
+        match current_track.seek_time(position) {
+            Ok(_) => send_msg(ctx, msg, &format!("Seeked to {}", format_duration(position))).await,
+            Err(reason) => send_msg(ctx, msg, &format!("Error seeking audio: {:?}", reason)).await,
+        }
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use serde::Deserialize;
+use std::{process::Stdio, time::Duration};
+use tokio::process::Command;
+
+use super::TrackSource;
+use crate::{Result, ResultExt};
+
+// youtube-dl's placeholder titles for entries that can't be played
+const UNAVAILABLE_TITLES: &[&str] = &["[Private video]", "[Deleted video]"];
+
+pub struct Playlist {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+pub struct PlaylistEntry {
+    pub url: String,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl PlaylistEntry {
+    // the entry is only looked up by youtube-dl once it's about to play
+    pub fn into_source(self) -> TrackSource {
+        TrackSource::LazyYtdl { url: self.url, title: self.title, duration: self.duration }
+    }
+}
+
+// the parts of youtube-dl's `--flat-playlist` JSON output that are used
+#[derive(Deserialize)]
+struct FlatPlaylist {
+    #[serde(rename = "_type")]
+    kind: Option<String>,
+    title: Option<String>,
+    #[serde(default)]
+    entries: Vec<Option<FlatEntry>>,
+}
+
+#[derive(Deserialize)]
+struct FlatEntry {
+    url: Option<String>,
+    webpage_url: Option<String>,
+    ie_key: Option<String>,
+    title: Option<String>,
+    duration: Option<f64>,
+}
+
+impl FlatEntry {
+    // youtube-dl only gives the video ID for YouTube entries, while yt-dlp gives the full URL
+    fn into_entry(self) -> Option<PlaylistEntry> {
+        if self.title.as_deref().is_some_and(|title| UNAVAILABLE_TITLES.contains(&title)) {
+            return None;
+        }
+
+        let url = match (self.url, self.webpage_url) {
+            (_, Some(webpage_url)) => webpage_url,
+            (Some(url), None) if url.starts_with("http") => url,
+            (Some(id), None) if self.ie_key.as_deref() == Some("Youtube") => format!("https://www.youtube.com/watch?v={}", id),
+            _ => return None,
+        };
+        let duration = self.duration.filter(|duration| duration.is_finite() && *duration >= 0.0).map(Duration::from_secs_f64);
+
+        Some(PlaylistEntry { url, title: self.title, duration })
+    }
+}
+
+// checks for YouTube playlist and SoundCloud set URLs, so single videos don't need an extra youtube-dl call
+// links to a video in a playlist or mix, such as `watch?v=X&list=RD...`, only play the video that was shared
+pub fn is_playlist_url(url: &str) -> bool {
+    let youtube_playlist = url.contains("/playlist?list=") || (url.contains("/playlist?") && url.contains("&list="));
+    let soundcloud_set = url.contains("soundcloud.com/") && url.contains("/sets/");
+    youtube_playlist || soundcloud_set
+}
+
+// lists the entries of a playlist without looking up each one, or returns `None` if the URL isn't a playlist
+// youtube-dl treats `ytsearchN:` queries as playlists too
+pub async fn fetch_playlist(url: &str) -> Result<Option<Playlist>> {
+    let output = Command::new("youtube-dl")
+        .args(["--flat-playlist", "-J", "--ignore-config", "--no-warnings", url])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .chain_err(|| "Unable to run youtube-dl")?;
+    if !output.status.success() {
+        return Err(format!("youtube-dl failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    let playlist: FlatPlaylist = serde_json::from_slice(&output.stdout).chain_err(|| "Invalid youtube-dl output")?;
+    if playlist.kind.as_deref() != Some("playlist") {
+        return Ok(None);
+    }
+
+    Ok(Some(Playlist {
+        title: playlist.title,
+        entries: playlist.entries.into_iter().flatten().filter_map(FlatEntry::into_entry).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playlist_links_are_playlists() {
+        assert!(is_playlist_url("https://www.youtube.com/playlist?list=PL1234"));
+        assert!(is_playlist_url("https://music.youtube.com/playlist?list=OLAK5uy"));
+        assert!(is_playlist_url("https://www.youtube.com/playlist?si=abc&list=PL1234"));
+        assert!(is_playlist_url("https://soundcloud.com/artist/sets/album-name"));
+    }
+
+    #[test]
+    fn videos_in_playlists_and_mixes_are_single_videos() {
+        assert!(!is_playlist_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ&start_radio=1"));
+        assert!(!is_playlist_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1234&index=3"));
+        assert!(!is_playlist_url("https://youtu.be/dQw4w9WgXcQ?list=PL1234"));
+        assert!(!is_playlist_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(!is_playlist_url("https://soundcloud.com/artist/track-name"));
+    }
+}
@@ -0,0 +1,208 @@
+use rand::{seq::SliceRandom, thread_rng};
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::{channel::Message, misc::Mentionable},
+};
+use std::time::Duration;
+
+use super::{format_duration, track_request, track_title, DJ_CHECK, MAX_PAGE_LENGTH, MAX_TITLE_LENGTH};
+use crate::{send_msg, shorten};
+
+const TRACKS_PER_PAGE: usize = 10;
+
+// lists the tracks in the audio queue, 10 per page
+#[command]
+#[aliases(q)]
+#[only_in(guilds)]
+async fn queue(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    let tracks = if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+        handler.queue().current_queue()
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+        return Ok(());
+    };
+
+    if tracks.is_empty() {
+        send_msg(ctx, msg, "The audio queue is empty").await;
+        return Ok(());
+    }
+
+    let page_count = tracks.len().div_ceil(TRACKS_PER_PAGE);
+    let page = args.single::<usize>().unwrap_or(1).clamp(1, page_count);
+
+    let total_duration = tracks.iter().filter_map(|track| track.metadata().duration).sum::<Duration>();
+    let mut queue_string = format!("Audio queue (page {}/{}, {} tracks, {} total):\n", page, page_count, tracks.len(), format_duration(total_duration));
+
+    for (position, track) in tracks.iter().enumerate().skip((page - 1) * TRACKS_PER_PAGE).take(TRACKS_PER_PAGE) {
+        let position_string = if position == 0 { "Now playing".to_string() } else { position.to_string() };
+        let duration_string = track.metadata().duration.map(format_duration).unwrap_or_else(|| "unknown duration".to_string());
+        let requester_string = match track_request(track).await {
+            Some(request) => format!(", requested by {}", request.user_id.mention()),
+            None => String::new(),
+        };
+        let line = format!("    {}: {} ({}{})\n", position_string, shorten(&track_title(track), MAX_TITLE_LENGTH), duration_string, requester_string);
+        if queue_string.len() + line.len() > MAX_PAGE_LENGTH {
+            queue_string.push_str("    ...\n");
+            break;
+        }
+        queue_string.push_str(&line);
+    }
+
+    // requesters are mentioned without being pinged
+    if let Err(reason) = msg.channel_id.send_message(&ctx.http, |m| {
+        m.content(&queue_string).allowed_mentions(|mentions| mentions.empty_parse())
+    }).await {
+        println!("Error sending message: {:?}", reason);
+    }
+
+    Ok(())
+}
+
+// removes the track at the specified queue position
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let position = match args.single::<usize>() {
+        Ok(position) => position,
+        Err(_) => {
+            send_msg(ctx, msg, "Usage: `remove <position>` (see `queue` for positions)").await;
+            return Ok(());
+        },
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        // position 0 is the current track, which should be skipped instead
+        let removed = handler.queue().modify_queue(|queue| {
+            if position >= 1 && position < queue.len() { queue.remove(position) } else { None }
+        });
+
+        match removed {
+            Some(track) => {
+                let _ = track.stop();
+                send_msg(ctx, msg, &format!("Removed {} from the queue", track_title(&track))).await;
+            },
+            None => send_msg(ctx, msg, &format!("No queued track at position {}", position)).await,
+        }
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// moves a queued track to another position
+#[command("move")]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn move_track(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let (from, to) = match (args.single::<usize>(), args.single::<usize>()) {
+        (Ok(from), Ok(to)) => (from, to),
+        _ => {
+            send_msg(ctx, msg, "Usage: `move <from> <to>` (see `queue` for positions)").await;
+            return Ok(());
+        },
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        // the current track stays where it is, so only positions after it are valid
+        let moved = handler.queue().modify_queue(|queue| {
+            let valid_positions = 1..queue.len();
+            if !valid_positions.contains(&from) || !valid_positions.contains(&to) {
+                return None;
+            }
+            let track = queue.remove(from)?;
+            let title = track_title(&track);
+            queue.insert(to, track);
+            Some(title)
+        });
+
+        match moved {
+            Some(title) => send_msg(ctx, msg, &format!("Moved {} to position {}", title, to)).await,
+            None => send_msg(ctx, msg, "Both positions must refer to queued tracks (see `queue` for positions)").await,
+        }
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// shuffles the queued tracks, leaving the current track playing
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn shuffle(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        handler.queue().modify_queue(|queue| {
+            if queue.len() > 2 {
+                queue.make_contiguous()[1..].shuffle(&mut thread_rng());
+            }
+        });
+
+        send_msg(ctx, msg, "Shuffled the audio queue").await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// removes every queued track, leaving the current track playing
+#[command]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn clear(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let handler = handler_lock.lock().await;
+
+        let removed = handler.queue().modify_queue(|queue| {
+            if queue.len() > 1 { queue.drain(1..).collect() } else { Vec::new() }
+        });
+        for track in &removed {
+            let _ = track.stop();
+        }
+
+        send_msg(ctx, msg, &format!("Removed {} tracks from the queue", removed.len())).await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
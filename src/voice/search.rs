@@ -0,0 +1,133 @@
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::channel::{Message, ReactionType},
+};
+use std::time::Duration;
+
+use super::{format_duration, join_impl, playlist, queue_source, TrackSource};
+use crate::{config, react_msg, send_msg, settings};
+
+const SEARCH_RESULTS: usize = 5;
+const NUMBER_EMOJIS: [&str; SEARCH_RESULTS] = ["1\u{fe0f}\u{20e3}", "2\u{fe0f}\u{20e3}", "3\u{fe0f}\u{20e3}", "4\u{fe0f}\u{20e3}", "5\u{fe0f}\u{20e3}"];
+
+// lists the top YouTube results for a query, then queues the one the requester picks
+#[command]
+#[only_in(guilds)]
+async fn search(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let query = args.rest().trim();
+    if query.is_empty() {
+        send_msg(ctx, msg, "Usage: `search <query>`").await;
+        return Ok(());
+    }
+
+    let mut results = match playlist::fetch_playlist(&format!("ytsearch{}:{}", SEARCH_RESULTS, query)).await {
+        Ok(Some(results)) if !results.entries.is_empty() => results.entries,
+        Ok(_) => {
+            send_msg(ctx, msg, "No results found").await;
+            return Ok(());
+        },
+        Err(reason) => {
+            println!("Error searching: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error searching: {}", reason)).await;
+            return Ok(());
+        },
+    };
+    results.truncate(SEARCH_RESULTS);
+    let result_count = results.len();
+
+    let mut results_string = "Search results:\n".to_string();
+    for (index, result) in results.iter().enumerate() {
+        let title = result.title.as_deref().unwrap_or("Unknown title");
+        let duration_string = result.duration.map(format_duration).unwrap_or_else(|| "unknown duration".to_string());
+        results_string.push_str(&format!("    {}: {} ({})\n", index + 1, title, duration_string));
+    }
+    results_string.push_str("Reply with a number or react to pick a track, or reply `cancel`");
+
+    let results_msg = match msg.channel_id.say(&ctx.http, &results_string).await {
+        Ok(results_msg) => results_msg,
+        Err(reason) => {
+            println!("Error sending message: {:?}", reason);
+            return Ok(());
+        },
+    };
+
+    // reacting takes a while, so it happens in the background to avoid missing early replies
+    {
+        let ctx = ctx.clone();
+        let results_msg = results_msg.clone();
+        tokio::spawn(async move {
+            for emoji in NUMBER_EMOJIS.iter().take(result_count) {
+                react_msg(&ctx, &results_msg, ReactionType::Unicode(emoji.to_string())).await;
+            }
+        });
+    }
+
+    let timeout = Duration::from_secs(config::get(ctx).await.search_timeout);
+    let choice = tokio::select! {
+        Some(reply) = msg.author.await_reply(ctx)
+            .channel_id(msg.channel_id)
+            .timeout(timeout)
+            .filter(move |reply| parse_choice(&reply.content, result_count).is_some()) => {
+            parse_choice(&reply.content, result_count).unwrap().ok_or("Search cancelled")
+        },
+        Some(reaction) = results_msg.await_reaction(ctx)
+            .author_id(msg.author.id)
+            .added(true)
+            .removed(false)
+            .timeout(timeout)
+            .filter(move |reaction| NUMBER_EMOJIS[..result_count].iter().any(|emoji| reaction.emoji.unicode_eq(emoji))) => {
+            let emoji = &reaction.as_inner_ref().emoji;
+            Ok(NUMBER_EMOJIS.iter().position(|number_emoji| emoji.unicode_eq(number_emoji)).unwrap())
+        },
+        else => Err("Search timed out"),
+    };
+
+    let index = match choice {
+        Ok(index) => index,
+        Err(reason) => {
+            send_msg(ctx, msg, reason).await;
+            return Ok(());
+        },
+    };
+    let source = TrackSource::Ytdl(results.swap_remove(index).url);
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    if manager.get(guild_id).is_none() {
+        let _ = join_impl(ctx, msg).await;
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let volume = {
+            let settings_lock = settings::get(ctx).await;
+            let settings = settings_lock.read().await;
+            settings.guild(guild_id).map_or(settings::DEFAULT_VOLUME, |guild_settings| guild_settings.volume)
+        };
+
+        queue_source(ctx, msg, &handler_lock, source, volume).await;
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+// `Some(None)` for `cancel`, `Some(Some(index))` for a valid result number
+fn parse_choice(content: &str, result_count: usize) -> Option<Option<usize>> {
+    let content = content.trim();
+    if content.eq_ignore_ascii_case("cancel") {
+        return Some(None);
+    }
+
+    match content.parse::<usize>() {
+        Ok(number) if (1..=result_count).contains(&number) => Some(Some(number - 1)),
+        _ => None,
+    }
+}
@@ -0,0 +1,559 @@
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::{
+        channel::Message,
+        misc::Mentionable,
+        prelude::{ChannelId, GuildId},
+    },
+    prelude::{Mutex, TypeMapKey},
+};
+use songbird::{
+    input::{error::Result as InputResult, Input, Metadata},
+    Event, EventContext, EventHandler as VoiceEventHandler, Songbird,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, atomic::{AtomicBool, Ordering}},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    enqueue,
+    filter::{filtered_input, set_track_filter, track_filter, Filter},
+    idle::listeners,
+    join_channel, player_states, track_request, LoopMode, PlayerStates, TrackRequest, TrackSource, DJ_CHECK,
+};
+use crate::{config, send_channel_msg, send_msg, settings, storage::Storage, Result};
+
+// stored in the data directory
+pub const SESSIONS_FILE_NAME: &str = "sessions.json";
+
+pub type Sessions = Arc<Mutex<SessionStore>>;
+
+// the playback position changes on every tick, so a change in position alone is only written this often
+const POSITION_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct SessionContainer;
+impl TypeMapKey for SessionContainer {
+    type Value = Sessions;
+}
+
+pub async fn sessions(ctx: &Context) -> Sessions {
+    let data = ctx.data.read().await;
+    data.get::<SessionContainer>().expect("Error getting voice sessions").clone()
+}
+
+// what a guild was listening to, saved regularly so it can be resumed after a restart
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VoiceSession {
+    pub voice_channel_id: ChannelId,
+    pub text_channel_id: Option<ChannelId>,
+    // the first track is the one that was playing
+    pub tracks: Vec<SavedTrack>,
+    // how far into the first track playback was, in the original audio's time
+    pub position: Duration,
+    #[serde(default)]
+    pub loop_mode: LoopMode,
+}
+
+impl VoiceSession {
+    // whether the session changed enough since it was saved to be worth writing again
+    fn differs_from(&self, saved: &VoiceSession) -> bool {
+        self.voice_channel_id != saved.voice_channel_id
+            || self.text_channel_id != saved.text_channel_id
+            || self.tracks != saved.tracks
+            || self.loop_mode != saved.loop_mode
+            || self.position.abs_diff(saved.position) >= POSITION_SAVE_INTERVAL
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedTrack {
+    pub request: TrackRequest,
+    pub title: Option<String>,
+    // the original audio's duration, without the filter's change in speed
+    pub duration: Option<Duration>,
+    #[serde(default)]
+    pub filter: Filter,
+}
+
+impl SavedTrack {
+    // starts the track again, through the filter it was playing with
+    async fn create_input(&self) -> InputResult<Input> {
+        if self.filter == Filter::Off {
+            return self.input_source().create_input().await;
+        }
+
+        let source_url = match &self.request.source {
+            TrackSource::Ytdl(url) | TrackSource::LazyYtdl { url, .. } | TrackSource::Url { url, .. } if url.starts_with("http") => Some(url.clone()),
+            _ => None,
+        };
+        let metadata = Metadata {
+            title: self.title.clone(),
+            duration: self.duration.map(|duration| duration.div_f64(self.filter.speed())),
+            source_url,
+            channels: Some(2),
+            sample_rate: Some(48000),
+            ..Default::default()
+        };
+        filtered_input(self.input_source(), self.filter, metadata).await
+    }
+
+    // links to Discord attachments stop working after a while, they say when in their `ex` parameter
+    fn expired(&self, now: SystemTime) -> bool {
+        let url = match &self.request.source {
+            TrackSource::Url { url, .. } => url,
+            _ => return false,
+        };
+        let expires_at = reqwest::Url::parse(url).ok()
+            .and_then(|url| url.query_pairs().find(|(key, _)| key == "ex").map(|(_, value)| value.into_owned()))
+            .and_then(|expires_at| u64::from_str_radix(&expires_at, 16).ok());
+
+        expires_at.is_some_and(|expires_at| UNIX_EPOCH + Duration::from_secs(expires_at) <= now)
+    }
+
+    // youtube-dl sources are resumed lazily with the saved metadata, so restoring a long queue doesn't look up every track
+    fn input_source(&self) -> TrackSource {
+        match &self.request.source {
+            TrackSource::Ytdl(url) if url.starts_with("http") => TrackSource::LazyYtdl {
+                url: url.clone(),
+                title: self.title.clone(),
+                duration: self.duration,
+            },
+            source => source.clone(),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SessionDocument {
+    #[serde(default)]
+    sessions: HashMap<GuildId, VoiceSession>,
+}
+
+// the saved sessions of every guild, written back to storage after every change
+pub struct SessionStore {
+    storage: Box<dyn Storage>,
+    document: SessionDocument,
+    // guilds that were offered to resume since their last call, since the cache is also ready again after reconnecting
+    offered: HashSet<GuildId>,
+}
+
+impl SessionStore {
+    // sessions are only worth keeping for a while, so ones that can't be read are dropped instead of failing to start
+    pub fn load(storage: Box<dyn Storage>) -> SessionStore {
+        let document = match storage.load() {
+            Ok(Some(document)) => serde_json::from_value(document).unwrap_or_else(|reason| {
+                println!("Error reading voice sessions, discarding them: {:?}", reason);
+                SessionDocument::default()
+            }),
+            Ok(None) => SessionDocument::default(),
+            Err(reason) => {
+                println!("Error loading voice sessions, discarding them: {:?}", reason);
+                SessionDocument::default()
+            },
+        };
+
+        SessionStore { storage, document, offered: HashSet::new() }
+    }
+
+    fn save(&self) -> Result<()> {
+        self.storage.save(&serde_json::to_value(&self.document)?)
+    }
+
+    pub fn get(&self, guild_id: GuildId) -> Option<&VoiceSession> {
+        self.document.sessions.get(&guild_id)
+    }
+
+    // replaces the guild's saved session, or forgets it if `session` is `None`
+    pub fn set(&mut self, guild_id: GuildId, session: Option<VoiceSession>) -> Result<()> {
+        let changed = match (self.document.sessions.get(&guild_id), &session) {
+            (Some(saved), Some(session)) => session.differs_from(saved),
+            (saved, session) => saved.is_some() != session.is_some(),
+        };
+        if !changed {
+            return Ok(());
+        }
+
+        match session {
+            Some(session) => self.document.sessions.insert(guild_id, session),
+            None => self.document.sessions.remove(&guild_id),
+        };
+        self.save()
+    }
+
+    pub fn forget_offer(&mut self, guild_id: GuildId) {
+        self.offered.remove(&guild_id);
+    }
+}
+
+// saves the state of a guild's call every 15 seconds
+pub struct SessionNotifier {
+    pub manager: Arc<Songbird>,
+    pub guild_id: GuildId,
+    pub player_states: PlayerStates,
+    pub sessions: Sessions,
+    // whether this call has saved a session yet, so joining without playing anything keeps the previous one
+    pub saved: AtomicBool,
+}
+
+#[async_trait]
+impl VoiceEventHandler for SessionNotifier {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        let (voice_channel_id, tracks) = match self.manager.get(self.guild_id) {
+            Some(handler_lock) => {
+                let handler = handler_lock.lock().await;
+                match handler.current_channel() {
+                    Some(channel_id) => (ChannelId(channel_id.0), handler.queue().current_queue()),
+                    // disconnected calls keep the last session, so it can still be resumed
+                    None => return None,
+                }
+            },
+            None => return None,
+        };
+
+        // positions and durations are saved in the original audio's time, since some filters change the speed
+        let position = match tracks.first() {
+            Some(current_track) => {
+                let position = current_track.get_info().await.map(|track_state| track_state.position).unwrap_or_default();
+                position.mul_f64(track_filter(current_track).await.speed())
+            },
+            None => Duration::default(),
+        };
+
+        let mut saved_tracks = Vec::new();
+        for track in &tracks {
+            // soundboard clips and other tracks that weren't requested aren't in the queue
+            if let Some(request) = track_request(track).await {
+                let filter = track_filter(track).await;
+                let metadata = track.metadata();
+                saved_tracks.push(SavedTrack {
+                    request,
+                    title: metadata.title.clone(),
+                    duration: metadata.duration.map(|duration| duration.mul_f64(filter.speed())),
+                    filter,
+                });
+            }
+        }
+
+        let session = if saved_tracks.is_empty() {
+            if !self.saved.load(Ordering::Relaxed) {
+                return None;
+            }
+            None
+        } else {
+            self.saved.store(true, Ordering::Relaxed);
+            let player_states = self.player_states.read().await;
+            let player_state = player_states.get(&self.guild_id);
+            Some(VoiceSession {
+                voice_channel_id,
+                text_channel_id: player_state.and_then(|player_state| player_state.text_channel_id),
+                tracks: saved_tracks,
+                position,
+                loop_mode: player_state.map(|player_state| player_state.loop_mode).unwrap_or_default(),
+            })
+        };
+
+        if let Err(reason) = self.sessions.lock().await.set(self.guild_id, session) {
+            println!("Error saving voice session: {:?}", reason);
+        }
+
+        None
+    }
+}
+
+// how many tracks of a session were queued again, and how many were left out since their links expired
+pub struct Restored {
+    pub queued: usize,
+    pub expired: usize,
+}
+
+impl Restored {
+    fn message(&self, voice_channel_id: ChannelId) -> String {
+        let mut restored_string = format!("Resumed playing {} tracks in {}", self.queued, voice_channel_id.mention());
+        if self.expired > 0 {
+            restored_string.push_str(&format!(", {} attachments were left out since their links expired", self.expired));
+        }
+        restored_string
+    }
+}
+
+// rejoins the session's voice channel and queues its tracks again
+async fn restore_session(ctx: &Context, guild_id: GuildId, session: VoiceSession) -> std::result::Result<Restored, String> {
+    // resuming in an empty channel would only make the bot leave again for being alone
+    if listeners(&ctx.cache, guild_id, session.voice_channel_id).await.is_some_and(|listeners| listeners.is_empty()) {
+        return Err(format!("Nobody is in {}, join it first to resume playback", session.voice_channel_id.mention()));
+    }
+
+    join_channel(ctx, guild_id, session.voice_channel_id, session.text_channel_id).await
+        .map_err(|reason| format!("Failed to join voice channel: {:?}", reason))?;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+    let handler_lock = manager.get(guild_id).ok_or_else(|| "Not in a voice channel".to_string())?;
+
+    let volume = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        settings.guild(guild_id).map_or(settings::DEFAULT_VOLUME, |guild_settings| guild_settings.volume)
+    };
+    player_states(ctx).await.write().await.entry(guild_id).or_default().loop_mode = session.loop_mode;
+
+    let now = SystemTime::now();
+    let mut restored = Restored { queued: 0, expired: 0 };
+    for saved_track in &session.tracks {
+        if saved_track.expired(now) {
+            restored.expired += 1;
+            continue;
+        }
+        let input = match saved_track.create_input().await {
+            Ok(input) => input,
+            Err(reason) => {
+                println!("Error starting source: {:?}", reason);
+                continue;
+            },
+        };
+
+        let mut handler = handler_lock.lock().await;
+        let track_handle = enqueue(&mut handler, input, saved_track.request.clone(), volume as f32 / 100.0).await;
+        if saved_track.filter != Filter::Off {
+            set_track_filter(&track_handle, saved_track.filter).await;
+        }
+        if restored.queued == 0 {
+            if session.position > Duration::default() {
+                let _ = track_handle.seek_time(session.position.div_f64(saved_track.filter.speed()));
+            }
+            if session.loop_mode == LoopMode::Track {
+                let _ = track_handle.enable_loop();
+            }
+        }
+        restored.queued += 1;
+    }
+
+    // the bot may have left again while the tracks were being started, such as when everyone left the channel
+    if !manager.get(guild_id).is_some_and(|current_handler_lock| Arc::ptr_eq(&current_handler_lock, &handler_lock)) {
+        return Err("Left the voice channel while resuming playback".to_string());
+    }
+
+    Ok(restored)
+}
+
+// called once the cache is ready, resumes saved sessions of guilds that opted in and offers it to the rest
+pub async fn offer_restore(ctx: &Context) {
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    let saved_sessions = sessions(ctx).await.lock().await.document.sessions.clone();
+
+    for (guild_id, session) in saved_sessions {
+        // the call survived, so there's nothing to resume
+        if let Some(handler_lock) = manager.get(guild_id) {
+            if handler_lock.lock().await.current_channel().is_some() {
+                continue;
+            }
+        }
+        if !sessions(ctx).await.lock().await.offered.insert(guild_id) {
+            continue;
+        }
+
+        let (auto_resume, prefix) = {
+            let settings_lock = settings::get(ctx).await;
+            let settings = settings_lock.read().await;
+            let guild_settings = settings.guild(guild_id);
+            (
+                guild_settings.is_some_and(|guild_settings| guild_settings.auto_resume),
+                guild_settings.and_then(|guild_settings| guild_settings.prefix.clone()),
+            )
+        };
+        let prefix = match prefix {
+            Some(prefix) => prefix,
+            None => config::get(ctx).await.prefix.clone(),
+        };
+        let text_channel_id = session.text_channel_id;
+
+        if auto_resume {
+            let voice_channel_id = session.voice_channel_id;
+            let result = restore_session(ctx, guild_id, session).await;
+            // sessions without a text channel are resumed without saying so
+            if let Some(text_channel_id) = text_channel_id {
+                match result {
+                    Ok(restored) => send_channel_msg(&ctx.http, text_channel_id, &format!("{} after restarting", restored.message(voice_channel_id))).await,
+                    Err(reason) => send_channel_msg(&ctx.http, text_channel_id, &format!(
+                        "Unable to resume playback after restarting: {}\nUse `{}session restore` to try again", reason, prefix,
+                    )).await,
+                }
+            }
+        } else if let Some(text_channel_id) = text_channel_id {
+            send_channel_msg(&ctx.http, text_channel_id, &format!(
+                "The bot restarted while playing {} tracks in {}, use `{}session restore` to resume or `{}session discard` to forget them",
+                session.tracks.len(), session.voice_channel_id.mention(), prefix, prefix,
+            )).await;
+        }
+    }
+}
+
+// shows the saved voice session of the current guild
+#[command]
+#[only_in(guilds)]
+#[sub_commands(session_restore, session_discard, session_auto)]
+async fn session(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let session = sessions(ctx).await.lock().await.get(guild_id).cloned();
+    let auto_resume = {
+        let settings_lock = settings::get(ctx).await;
+        let settings = settings_lock.read().await;
+        settings.guild(guild_id).is_some_and(|guild_settings| guild_settings.auto_resume)
+    };
+
+    let mut session_string = match session {
+        Some(session) => format!("Saved session: {} tracks in {}\n", session.tracks.len(), session.voice_channel_id.mention()),
+        None => "No saved session\n".to_string(),
+    };
+    session_string.push_str(&format!("Resuming automatically after a restart: {}", if auto_resume { "on" } else { "off" }));
+    send_msg(ctx, msg, &session_string).await;
+
+    Ok(())
+}
+
+// rejoins the saved voice channel and queues the saved tracks again
+#[command("restore")]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn session_restore(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+    if let Some(handler_lock) = manager.get(guild_id) {
+        if handler_lock.lock().await.current_channel().is_some() {
+            send_msg(ctx, msg, "Already in a voice channel, `leave` first to restore the saved session").await;
+            return Ok(());
+        }
+    }
+
+    let session = match sessions(ctx).await.lock().await.get(guild_id).cloned() {
+        Some(session) => session,
+        None => {
+            send_msg(ctx, msg, "No saved session").await;
+            return Ok(());
+        },
+    };
+
+    let voice_channel_id = session.voice_channel_id;
+    match restore_session(ctx, guild_id, session).await {
+        Ok(restored) => send_msg(ctx, msg, &restored.message(voice_channel_id)).await,
+        Err(reason) => send_msg(ctx, msg, &reason).await,
+    }
+
+    Ok(())
+}
+
+// forgets the saved voice session
+#[command("discard")]
+#[only_in(guilds)]
+#[checks(DJ)]
+async fn session_discard(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    if let Err(reason) = sessions(ctx).await.lock().await.set(guild_id, None) {
+        println!("Error saving voice session: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving voice session: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, "Discarded the saved session").await;
+
+    Ok(())
+}
+
+// sets whether the saved session is resumed without asking after a restart
+#[command("auto")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn session_auto(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let enabled = match args.single::<String>().as_deref() {
+        Ok("on") => true,
+        Ok("off") => false,
+        _ => {
+            send_msg(ctx, msg, "Usage: `session auto on|off`").await;
+            return Ok(());
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+
+    let settings_lock = settings::get(ctx).await;
+    let mut settings = settings_lock.write().await;
+
+    settings.guild_mut(guild_id).auto_resume = enabled;
+
+    if let Err(reason) = settings.save() {
+        println!("Error saving settings: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving settings: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Resuming automatically after a restart turned {}", if enabled { "on" } else { "off" })).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serenity::model::prelude::UserId;
+
+    fn saved_track(source: TrackSource) -> SavedTrack {
+        SavedTrack {
+            request: TrackRequest { user_id: UserId(1), requested_at: Utc::now(), source },
+            title: None,
+            duration: None,
+            filter: Filter::Off,
+        }
+    }
+
+    fn attachment(url: &str) -> SavedTrack {
+        saved_track(TrackSource::Url { url: url.to_string(), title: "clip.mp3".to_string() })
+    }
+
+    #[test]
+    fn expired_attachment_links_are_detected() {
+        let now = UNIX_EPOCH + Duration::from_secs(0x6700_0000);
+        assert!(attachment("https://cdn.discordapp.com/attachments/1/2/clip.mp3?ex=66ffffff&is=1&hm=2").expired(now));
+        assert!(!attachment("https://cdn.discordapp.com/attachments/1/2/clip.mp3?ex=67000001&is=1&hm=2").expired(now));
+        assert!(!attachment("https://example.com/clip.mp3").expired(now));
+        assert!(!saved_track(TrackSource::Ytdl("https://example.com/watch?ex=1".to_string())).expired(now));
+    }
+
+    #[test]
+    fn tracks_saved_without_a_filter_are_unfiltered() {
+        let mut value = serde_json::to_value(saved_track(TrackSource::Ytdl("https://example.com".to_string()))).unwrap();
+        assert_eq!(value["filter"], "off");
+        value.as_object_mut().unwrap().remove("filter");
+        assert!(serde_json::from_value::<SavedTrack>(value).unwrap().filter == Filter::Off);
+    }
+
+    #[test]
+    fn small_position_changes_are_not_saved() {
+        let session = VoiceSession {
+            voice_channel_id: ChannelId(1),
+            text_channel_id: None,
+            tracks: vec![saved_track(TrackSource::Ytdl("https://example.com".to_string()))],
+            position: Duration::from_secs(10),
+            loop_mode: LoopMode::Off,
+        };
+        let mut moved = session.clone();
+        moved.position += POSITION_SAVE_INTERVAL / 2;
+        assert!(!moved.differs_from(&session));
+        moved.position += POSITION_SAVE_INTERVAL;
+        assert!(moved.differs_from(&session));
+        moved.position = session.position;
+        moved.tracks[0].filter = Filter::Nightcore;
+        assert!(moved.differs_from(&session));
+    }
+}
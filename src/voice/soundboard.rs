@@ -0,0 +1,246 @@
+use serenity::{
+    client::Context,
+    framework::standard::{
+        Args, CommandResult,
+        macros::command,
+    },
+    model::{channel::Message, prelude::GuildId},
+};
+use std::{fs, io, path::{Path, PathBuf}};
+
+use super::{audio_attachments, dj_role_string, is_dj, join_impl, library::AUDIO_EXTENSIONS};
+use crate::{config, send_msg, settings};
+
+const MAX_CLIPS_PER_GUILD: usize = 50;
+const MAX_CLIP_SIZE: u64 = 4 * 1024 * 1024;
+const MAX_NAME_LENGTH: usize = 32;
+// subcommand names can't be used for clips, since `sb <name>` would run the subcommand instead
+const RESERVED_NAMES: &[&str] = &["add", "list", "remove"];
+
+// every guild's clips are stored in their own directory, named after the clip
+fn guild_dir(data_dir: &Path, guild_id: GuildId) -> PathBuf {
+    data_dir.join("soundboard").join(guild_id.to_string())
+}
+
+// lists the clips of a guild as (name, path) pairs sorted by name
+fn clips(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(reason) if reason.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(reason) => return Err(reason),
+    };
+
+    let mut clips = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+            clips.push((name.to_string(), path.clone()));
+        }
+    }
+    clips.sort();
+
+    Ok(clips)
+}
+
+fn find_clip(dir: &Path, name: &str) -> io::Result<Option<PathBuf>> {
+    Ok(clips(dir)?.into_iter().find(|(clip_name, _)| clip_name == name).map(|(_, path)| path))
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && !RESERVED_NAMES.contains(&name)
+}
+
+// plays a soundboard clip over the current audio, leaving the queue as it is
+#[command]
+#[aliases(soundboard)]
+#[only_in(guilds)]
+#[sub_commands(sb_add, sb_list, sb_remove)]
+async fn sb(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = match args.single::<String>() {
+        Ok(name) => name.to_lowercase(),
+        Err(_) => return list_impl(ctx, msg).await,
+    };
+
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let dir = guild_dir(&config::get(ctx).await.data_dir, guild_id);
+    let path = match find_clip(&dir, &name) {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            send_msg(ctx, msg, &format!("No soundboard clip named `{}` (see `sb list`)", name)).await;
+            return Ok(());
+        },
+        Err(reason) => {
+            println!("Error reading soundboard: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error reading soundboard: {:?}", reason)).await;
+            return Ok(());
+        },
+    };
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    // clips play over the queue, so once the bot is in a call they're for DJs only
+    if manager.get(guild_id).is_none() {
+        let _ = join_impl(ctx, msg).await;
+    } else if !is_dj(ctx, msg).await {
+        send_msg(ctx, msg, &format!("Only members with {} can play clips while the bot is in a voice channel", dj_role_string(ctx, msg).await)).await;
+        return Ok(());
+    }
+
+    if let Some(handler_lock) = manager.get(guild_id) {
+        let input = match songbird::ffmpeg(&path).await {
+            Ok(input) => input,
+            Err(reason) => {
+                println!("Error starting source: {:?}", reason);
+                send_msg(ctx, msg, &format!("Error starting source: {:?}", reason)).await;
+                return Ok(());
+            },
+        };
+
+        let volume = {
+            let settings_lock = settings::get(ctx).await;
+            let settings = settings_lock.read().await;
+            settings.guild(guild_id).map_or(settings::DEFAULT_VOLUME, |guild_settings| guild_settings.volume)
+        };
+
+        // tracks played outside of the queue are mixed with whatever the queue is playing
+        let mut handler = handler_lock.lock().await;
+        let track_handle = handler.play_source(input);
+        let _ = track_handle.set_volume(volume as f32 / 100.0);
+    } else {
+        send_msg(ctx, msg, "Not in a voice channel").await;
+    }
+
+    Ok(())
+}
+
+#[command("list")]
+#[only_in(guilds)]
+async fn sb_list(ctx: &Context, msg: &Message) -> CommandResult {
+    list_impl(ctx, msg).await
+}
+
+// saves the attached audio file as a soundboard clip
+#[command("add")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn sb_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = match args.single::<String>() {
+        Ok(name) if valid_name(&name.to_lowercase()) => name.to_lowercase(),
+        _ => {
+            send_msg(ctx, msg, &format!("Usage: `sb add <name>` with an attached audio file (names can be up to {} letters, numbers, `-` and `_`)", MAX_NAME_LENGTH)).await;
+            return Ok(());
+        },
+    };
+
+    let attachment = match audio_attachments(msg).first() {
+        Some(attachment) => (*attachment).clone(),
+        None => {
+            send_msg(ctx, msg, "Attach an audio file, or reply to a message with one").await;
+            return Ok(());
+        },
+    };
+    // the extension is kept so ffmpeg can tell the format apart
+    let extension = match attachment.filename.rsplit_once('.') {
+        Some((_, extension)) if AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()) => extension.to_lowercase(),
+        _ => {
+            send_msg(ctx, msg, &format!("Soundboard clips must be {} files", AUDIO_EXTENSIONS.join(", "))).await;
+            return Ok(());
+        },
+    };
+    if attachment.size > MAX_CLIP_SIZE {
+        send_msg(ctx, msg, &format!("Soundboard clips can be at most {} MB", MAX_CLIP_SIZE / 1024 / 1024)).await;
+        return Ok(());
+    }
+
+    let guild_id = msg.guild_id.unwrap();
+    let dir = guild_dir(&config::get(ctx).await.data_dir, guild_id);
+
+    let existing_clips = match clips(&dir) {
+        Ok(existing_clips) => existing_clips,
+        Err(reason) => {
+            println!("Error reading soundboard: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error reading soundboard: {:?}", reason)).await;
+            return Ok(());
+        },
+    };
+    if existing_clips.iter().any(|(clip_name, _)| *clip_name == name) {
+        send_msg(ctx, msg, &format!("A soundboard clip named `{}` already exists, remove it first", name)).await;
+        return Ok(());
+    }
+    if existing_clips.len() >= MAX_CLIPS_PER_GUILD {
+        send_msg(ctx, msg, &format!("This server already has the maximum of {} soundboard clips", MAX_CLIPS_PER_GUILD)).await;
+        return Ok(());
+    }
+
+    let content = match attachment.download().await {
+        Ok(content) => content,
+        Err(reason) => {
+            println!("Error downloading attachment: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error downloading attachment: {:?}", reason)).await;
+            return Ok(());
+        },
+    };
+
+    let path = dir.join(format!("{}.{}", name, extension));
+    if let Err(reason) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, content)) {
+        println!("Error saving soundboard clip: {:?}", reason);
+        send_msg(ctx, msg, &format!("Error saving soundboard clip: {:?}", reason)).await;
+        return Ok(());
+    }
+
+    send_msg(ctx, msg, &format!("Added soundboard clip `{}`", name)).await;
+
+    Ok(())
+}
+
+// deletes a soundboard clip
+#[command("remove")]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+async fn sb_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = match args.single::<String>() {
+        Ok(name) => name.to_lowercase(),
+        Err(_) => {
+            send_msg(ctx, msg, "Usage: `sb remove <name>`").await;
+            return Ok(());
+        },
+    };
+
+    let guild_id = msg.guild_id.unwrap();
+    let dir = guild_dir(&config::get(ctx).await.data_dir, guild_id);
+
+    match find_clip(&dir, &name).and_then(|path| path.map(fs::remove_file).transpose()) {
+        Ok(Some(())) => send_msg(ctx, msg, &format!("Removed soundboard clip `{}`", name)).await,
+        Ok(None) => send_msg(ctx, msg, &format!("No soundboard clip named `{}`", name)).await,
+        Err(reason) => {
+            println!("Error removing soundboard clip: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error removing soundboard clip: {:?}", reason)).await;
+        },
+    }
+
+    Ok(())
+}
+
+async fn list_impl(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+    let dir = guild_dir(&config::get(ctx).await.data_dir, guild_id);
+
+    match clips(&dir) {
+        Ok(clips) if clips.is_empty() => send_msg(ctx, msg, "This server doesn't have any soundboard clips, add one with `sb add <name>`").await,
+        Ok(clips) => {
+            let names = clips.iter().map(|(name, _)| format!("`{}`", name)).collect::<Vec<_>>();
+            send_msg(ctx, msg, &format!("Soundboard clips ({}): {}", clips.len(), names.join(", "))).await;
+        },
+        Err(reason) => {
+            println!("Error reading soundboard: {:?}", reason);
+            send_msg(ctx, msg, &format!("Error reading soundboard: {:?}", reason)).await;
+        },
+    }
+
+    Ok(())
+}
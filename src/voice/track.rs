@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::{async_trait, model::prelude::UserId};
+use songbird::{
+    input::{
+        self, children_to_reader,
+        error::{Error as InputError, Result as InputResult},
+        restartable::Restart,
+        Codec, Container, Input, Metadata, Restartable,
+    },
+    tracks::TrackHandle,
+    typemap::TypeMapKey,
+    Call,
+};
+use std::{path::PathBuf, process::{Command, Stdio}, time::Duration};
+
+// where a queued track's audio comes from, so the track can be recreated later
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum TrackSource {
+    // a URL (or `ytsearch1:` query) handled by youtube-dl
+    Ytdl(String),
+    // a URL handled by youtube-dl that is only looked up once it's about to play, such as a playlist entry
+    LazyYtdl {
+        url: String,
+        title: Option<String>,
+        duration: Option<Duration>,
+    },
+    // a local file played through ffmpeg
+    File(PathBuf),
+    // a direct link to an audio file played through ffmpeg, such as a Discord attachment
+    Url {
+        url: String,
+        title: String,
+    },
+}
+
+impl TrackSource {
+    // restartable inputs can be seeked and looped, and lazy ones only start downloading once they're about to play
+    pub async fn create_input(&self) -> InputResult<Input> {
+        match self {
+            TrackSource::Ytdl(url) => Ok(Restartable::ytdl(url.clone(), true).await?.into()),
+            TrackSource::LazyYtdl { url, title, duration } => {
+                let metadata = Metadata {
+                    title: title.clone(),
+                    duration: *duration,
+                    source_url: Some(url.clone()),
+                    channels: Some(2),
+                    sample_rate: Some(48000),
+                    ..Default::default()
+                };
+                Ok(Restartable::new(LazyYtdlRestarter { url: url.clone(), metadata }, true).await?.into())
+            },
+            TrackSource::File(path) => {
+                let mut input: Input = Restartable::ffmpeg(path.clone(), true).await?.into();
+                // songbird reads the title tag into `track`, and untagged files are named after the file instead
+                let title = input.metadata.track.clone()
+                    .or_else(|| path.file_name().map(|file_name| file_name.to_string_lossy().into_owned()));
+                input.metadata.title = title;
+                Ok(input)
+            },
+            TrackSource::Url { url, title } => {
+                let mut input: Input = Restartable::ffmpeg(url.clone(), true).await?.into();
+                input.metadata.title = Some(title.clone());
+                input.metadata.source_url = Some(url.clone());
+                Ok(input)
+            },
+        }
+    }
+}
+
+// like songbird's youtube-dl restarter, but uses metadata that's already known instead of asking youtube-dl for it up front
+struct LazyYtdlRestarter {
+    url: String,
+    metadata: Metadata,
+}
+
+#[async_trait]
+impl Restart for LazyYtdlRestarter {
+    async fn call_restart(&mut self, time: Option<Duration>) -> InputResult<Input> {
+        match time {
+            Some(time) => ffmpeg_from(&TrackSource::Ytdl(self.url.clone()), time, None),
+            None => input::ytdl(&self.url).await,
+        }
+    }
+
+    async fn lazy_init(&mut self) -> InputResult<(Option<Metadata>, Codec, Container)> {
+        Ok((Some(self.metadata.clone()), Codec::FloatPcm, Container::Raw))
+    }
+}
+
+// streams a source through ffmpeg starting at the given position, optionally through an audio filter chain
+pub fn ffmpeg_from(source: &TrackSource, time: Duration, audio_filter: Option<&str>) -> InputResult<Input> {
+    let mut children = Vec::new();
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg.args(["-ss", &format!("{:.3}", time.as_secs_f64()), "-i"]);
+
+    match source {
+        // youtube-dl streams are piped into ffmpeg, since only youtube-dl knows where the audio is
+        TrackSource::Ytdl(url) | TrackSource::LazyYtdl { url, .. } => {
+            let mut youtube_dl = Command::new("youtube-dl")
+                .args(["-f", "webm[abr>0]/bestaudio/best", "-R", "infinite", "--no-playlist", "--ignore-config", "--no-warnings", url, "-o", "-"])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .stdout(Stdio::piped())
+                .spawn()?;
+            ffmpeg.arg("-").stdin(youtube_dl.stdout.take().ok_or(InputError::Stdout)?);
+            children.push(youtube_dl);
+        },
+        TrackSource::File(path) => {
+            ffmpeg.arg(path).stdin(Stdio::null());
+        },
+        TrackSource::Url { url, .. } => {
+            ffmpeg.arg(url).stdin(Stdio::null());
+        },
+    }
+
+    if let Some(audio_filter) = audio_filter {
+        ffmpeg.args(["-af", audio_filter]);
+    }
+    let ffmpeg = ffmpeg
+        .args(["-f", "s16le", "-ac", "2", "-ar", "48000", "-acodec", "pcm_f32le", "-"])
+        .stderr(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    children.push(ffmpeg);
+
+    Ok(Input::new(true, children_to_reader::<f32>(children), Codec::FloatPcm, Container::Raw, None))
+}
+
+// attached to every queued track
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackRequest {
+    pub user_id: UserId,
+    pub requested_at: DateTime<Utc>,
+    pub source: TrackSource,
+}
+
+pub struct TrackRequestContainer;
+impl TypeMapKey for TrackRequestContainer {
+    type Value = TrackRequest;
+}
+
+// returns `None` for tracks that weren't queued through `enqueue`
+pub async fn track_request(track: &TrackHandle) -> Option<TrackRequest> {
+    track.typemap().read().await.get::<TrackRequestContainer>().cloned()
+}
+
+pub fn track_title(track: &TrackHandle) -> String {
+    let metadata = track.metadata();
+    metadata.title.clone()
+        .or_else(|| metadata.source_url.clone())
+        .unwrap_or_else(|| "Unknown title".to_string())
+}
+
+// adds an input to the end of the queue, `volume` being a fraction of the original volume
+pub async fn enqueue(handler: &mut Call, input: Input, request: TrackRequest, volume: f32) -> TrackHandle {
+    let (mut track, track_handle) = songbird::create_player(input);
+    track.set_volume(volume);
+    track_handle.typemap().write().await.insert::<TrackRequestContainer>(request);
+    handler.enqueue(track);
+
+    track_handle
+}
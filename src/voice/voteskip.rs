@@ -0,0 +1,113 @@
+use serenity::{
+    async_trait,
+    client::Context,
+    framework::standard::{CommandResult, macros::command},
+    model::{
+        channel::Message,
+        misc::Mentionable,
+        prelude::{ChannelId, UserId},
+    },
+};
+use songbird::{typemap::TypeMapKey, Event, EventContext, EventHandler as VoiceEventHandler, TrackEvent};
+use std::collections::HashSet;
+
+use super::idle::listeners;
+use crate::{config, send_msg};
+
+// the users who voted to skip a track, kept with the track so the votes are reset once it ends or loops
+struct SkipVotesContainer;
+impl TypeMapKey for SkipVotesContainer {
+    type Value = HashSet<UserId>;
+}
+
+// clears the votes each time a looping track starts over, so votes from an earlier repeat don't count
+struct SkipVoteResetter;
+
+#[async_trait]
+impl VoiceEventHandler for SkipVoteResetter {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(track_list) = ctx {
+            for (_, track_handle) in track_list.iter() {
+                if let Some(votes) = track_handle.typemap().write().await.get_mut::<SkipVotesContainer>() {
+                    votes.clear();
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// votes to skip the current track, which is skipped once enough of the listeners have voted
+#[command]
+#[only_in(guilds)]
+async fn voteskip(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild = msg.guild(&ctx.cache).await.unwrap();
+    let guild_id = guild.id;
+
+    let manager = songbird::get(ctx).await.expect("Error getting Songbird client").clone();
+
+    let (channel_id, current_track) = match manager.get(guild_id) {
+        Some(handler_lock) => {
+            let handler = handler_lock.lock().await;
+            match handler.current_channel() {
+                Some(channel_id) => (ChannelId(channel_id.0), handler.queue().current()),
+                None => {
+                    send_msg(ctx, msg, "Not in a voice channel").await;
+                    return Ok(());
+                },
+            }
+        },
+        None => {
+            send_msg(ctx, msg, "Not in a voice channel").await;
+            return Ok(());
+        },
+    };
+    let current_track = match current_track {
+        Some(current_track) => current_track,
+        None => {
+            send_msg(ctx, msg, "No audio track appears to be playing at the moment").await;
+            return Ok(());
+        },
+    };
+
+    let listeners = match listeners(&ctx.cache, guild_id, channel_id).await {
+        Some(listeners) => listeners,
+        None => {
+            send_msg(ctx, msg, "Unable to count the listeners in the voice channel").await;
+            return Ok(());
+        },
+    };
+    if !listeners.contains(&msg.author.id) {
+        send_msg(ctx, msg, &format!("Only people listening in {} can vote to skip", channel_id.mention())).await;
+        return Ok(());
+    }
+
+    let (new_vote, vote_count) = {
+        let mut typemap = current_track.typemap().write().await;
+        if !typemap.contains_key::<SkipVotesContainer>() {
+            typemap.insert::<SkipVotesContainer>(HashSet::new());
+            let _ = current_track.add_event(Event::Track(TrackEvent::Loop), SkipVoteResetter);
+        }
+        let votes = typemap.get_mut::<SkipVotesContainer>().unwrap();
+        let new_vote = votes.insert(msg.author.id);
+        // votes of people who have left the channel since don't count
+        (new_vote, votes.iter().filter(|user_id| listeners.contains(user_id)).count())
+    };
+
+    let voteskip_percent = config::get(ctx).await.voteskip_percent as usize;
+    let required_votes = (listeners.len() * voteskip_percent).div_ceil(100).max(1);
+
+    if vote_count >= required_votes {
+        match current_track.stop() {
+            Ok(_) => send_msg(ctx, msg, &format!("Vote passed ({}/{}), skipped audio", vote_count, required_votes)).await,
+            Err(reason) => send_msg(ctx, msg, &format!("Error skipping audio: {:?}", reason)).await,
+        }
+    } else if new_vote {
+        send_msg(ctx, msg, &format!("Voted to skip ({}/{} votes needed)", vote_count, required_votes)).await;
+    } else {
+        send_msg(ctx, msg, &format!("You already voted to skip this track ({}/{} votes needed)", vote_count, required_votes)).await;
+    }
+
+    Ok(())
+}